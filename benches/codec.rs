@@ -0,0 +1,77 @@
+use bytes::{BufMut, BytesMut};
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use std::hint::black_box;
+use uiop_dsp::codec::{encode_payload, parse_frame, parse_payload, payload_bytes};
+
+/// Frames as they arrive, without their terminator
+fn frames() -> Vec<(&'static str, String)> {
+    vec![
+        ("join", String::from("alice JOIN")),
+        (
+            "message",
+            String::from("alice MESSAGE Hello there, is anyone around to chat?"),
+        ),
+        (
+            "long_message",
+            format!("alice MESSAGE {}", "lorem ipsum ".repeat(42)),
+        ),
+        (
+            "challenge",
+            format!("server CHALLENGE 7 {}", "aZ09".repeat(16)),
+        ),
+    ]
+}
+
+/// Owned parsing copies the frame out of the read buffer, borrowed parsing views it in place
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    for (name, frame) in frames() {
+        let bytes = frame.as_bytes();
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_with_input(BenchmarkId::new("owned", name), bytes, |b, bytes| {
+            b.iter(|| parse_payload(String::from_utf8(bytes.to_vec()).unwrap()).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("borrowed", name), bytes, |b, bytes| {
+            b.iter(|| black_box(parse_frame(bytes).unwrap()))
+        });
+        group.bench_with_input(
+            BenchmarkId::new("borrowed_to_owned", name),
+            bytes,
+            |b, bytes| b.iter(|| parse_frame(bytes).unwrap().to_payload()),
+        );
+    }
+    group.finish();
+}
+
+/// Terminated frames ready for the socket, from a fresh Vec or a reused buffer
+fn encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode");
+    for (name, frame) in frames() {
+        let payload = parse_payload(frame.clone()).unwrap();
+        group.throughput(Throughput::Bytes(frame.len() as u64 + 1));
+        group.bench_with_input(BenchmarkId::new("owned", name), &payload, |b, payload| {
+            b.iter_batched(
+                || payload.clone(),
+                |payload| {
+                    let mut bytes = payload_bytes(payload);
+                    bytes.push(0u8);
+                    black_box(bytes)
+                },
+                BatchSize::SmallInput,
+            )
+        });
+        let mut buffer = BytesMut::new();
+        group.bench_with_input(BenchmarkId::new("buffer", name), &payload, |b, payload| {
+            b.iter(|| {
+                buffer.clear();
+                encode_payload(payload.view(), &mut buffer);
+                buffer.put_u8(0u8);
+                black_box(&buffer);
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, decode, encode);
+criterion_main!(benches);
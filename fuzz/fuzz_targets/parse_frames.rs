@@ -0,0 +1,18 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use std::sync::LazyLock;
+use tokio::runtime::{Builder, Runtime};
+use uiop_dsp::codec::{parse_frame, read_payload};
+
+static RUNTIME: LazyLock<Runtime> =
+    LazyLock::new(|| Builder::new_current_thread().build().unwrap());
+
+// Reads a byte stream like one from a server, frame by frame until it fails,
+// and parses the whole input as a single frame
+fuzz_target!(|data: &[u8]| {
+    let _ = parse_frame(data);
+    let mut stream = data;
+    let mut frame = vec![];
+    RUNTIME.block_on(async { while read_payload(&mut stream, &mut frame).await.is_ok() {} });
+});
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use uiop_dsp::codec::{parse_payload, payload_bytes};
+
+// Whatever parses has to parse the same way again once stringified
+fuzz_target!(|text: String| {
+    let Ok(payload) = parse_payload(text) else {
+        return;
+    };
+    let text = String::from_utf8(payload_bytes(payload.clone())).unwrap();
+    assert_eq!(parse_payload(text).ok(), Some(payload));
+});
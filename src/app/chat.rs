@@ -0,0 +1,881 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    ops::{Range, RangeInclusive},
+};
+
+use chrono::{DateTime, Local};
+use log::Level;
+use ratatui::{prelude::*, widgets::*};
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use super::{
+    links::{find_links, hyperlink, sanitize_url},
+    search::SearchQuery,
+    theme::username_hash,
+};
+
+/// Amount of chat lines kept in memory before the oldest ones are dropped
+const CHAT_CAPACITY: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatKind {
+    Message,
+    Join,
+    Quit,
+    Notice,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChatLine {
+    pub time: DateTime<Local>,
+    pub level: Level,
+    pub kind: ChatKind,
+    pub sender: Option<String>,
+    pub text: String,
+    /// Byte ranges of `text` which mention us
+    pub mentions: Vec<Range<usize>>,
+    /// Byte ranges of URLs in `text`
+    pub links: Vec<Range<usize>>,
+    /// How many times the same message was collapsed into this line
+    pub repeats: usize,
+}
+
+impl ChatLine {
+    fn new(level: Level, kind: ChatKind, sender: Option<String>, text: String) -> ChatLine {
+        ChatLine {
+            time: Local::now(),
+            level,
+            kind,
+            sender,
+            links: find_links(&text),
+            text,
+            mentions: vec![],
+            repeats: 1,
+        }
+    }
+
+    pub fn message(sender: String, text: String, mentions: Vec<Range<usize>>) -> ChatLine {
+        ChatLine {
+            mentions,
+            ..ChatLine::new(Level::Info, ChatKind::Message, Some(sender), text)
+        }
+    }
+
+    pub fn join(username: String) -> ChatLine {
+        let text = format!("User '{}' has joined the server", username);
+        ChatLine::new(Level::Debug, ChatKind::Join, Some(username), text)
+    }
+
+    pub fn quit(username: String) -> ChatLine {
+        let text = format!("User '{}' has left the server", username);
+        ChatLine::new(Level::Debug, ChatKind::Quit, Some(username), text)
+    }
+
+    pub fn notice(level: Level, text: String) -> ChatLine {
+        ChatLine::new(level, ChatKind::Notice, None, text)
+    }
+
+    pub fn is_mention(&self) -> bool {
+        !self.mentions.is_empty()
+    }
+}
+
+impl fmt::Display for ChatLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.kind, &self.sender) {
+            (ChatKind::Message, Some(sender)) => write!(f, "[{}] {}", sender, self.text)?,
+            _ => write!(f, "{}", self.text)?,
+        }
+        match self.repeats {
+            1 => Ok(()),
+            repeats => write!(f, " ×{}", repeats),
+        }
+    }
+}
+
+/// Finds words in incoming messages which should catch our attention
+pub struct MentionMatcher {
+    needles: Vec<String>,
+}
+
+impl MentionMatcher {
+    pub fn new(username: &str, highlight_words: &[String]) -> MentionMatcher {
+        let needles = std::iter::once(username)
+            .chain(highlight_words.iter().map(String::as_str))
+            .filter(|needle| !needle.is_empty())
+            .map(str::to_string)
+            .collect();
+        MentionMatcher { needles }
+    }
+
+    /// Case-insensitive whole-word matches, a leading '@' is included in the match
+    pub fn find(&self, text: &str) -> Vec<Range<usize>> {
+        let mut found: Vec<Range<usize>> = vec![];
+        for (start, _) in text.char_indices() {
+            if found.last().is_some_and(|last| start < last.end) {
+                continue;
+            }
+            let preceding = text[..start].chars().next_back();
+            if preceding.is_some_and(is_word_char) {
+                continue;
+            }
+            let matched = self
+                .needles
+                .iter()
+                .filter_map(|needle| match_ignore_case(&text[start..], needle))
+                .max();
+            let Some(len) = matched else { continue };
+            let end = start + len;
+            if text[end..].chars().next().is_some_and(is_word_char) {
+                continue;
+            }
+            let start = match preceding {
+                Some('@') => start - 1,
+                _ => start,
+            };
+            found.push(start..end);
+        }
+        found
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Byte length of `needle` matched at the start of `haystack`, if it matches
+fn match_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    let mut haystack_chars = haystack.char_indices();
+    for n in needle.chars() {
+        let (_, h) = haystack_chars.next()?;
+        if !h.to_lowercase().eq(n.to_lowercase()) {
+            return None;
+        }
+    }
+    Some(
+        haystack_chars
+            .next()
+            .map(|(index, _)| index)
+            .unwrap_or(haystack.len()),
+    )
+}
+
+/// In-memory chat history and its scroll position
+#[derive(Default)]
+pub struct ChatLog {
+    lines: VecDeque<ChatLine>,
+    /// Amount of lines dropped from the front, keeps line ids stable
+    dropped: usize,
+    /// Amount of rendered rows scrolled up from the bottom
+    scroll: usize,
+    /// Amount of rows visible during the last render
+    page_height: usize,
+    /// Where senders' usernames were drawn during the last render
+    sender_areas: Vec<(Rect, String)>,
+    /// Where links were drawn during the last render
+    link_areas: Vec<(Rect, String)>,
+    /// Id of a line to scroll into view during the next render
+    jump_to: Option<usize>,
+    /// Id of the newest line during the last render
+    rendered_until: Option<usize>,
+    /// Messages which arrived while scrolled up
+    new_messages: usize,
+    /// Id of the last line seen before new lines arrived while scrolled up
+    last_read: Option<usize>,
+    /// Id of the last line loaded from earlier sessions
+    history_end: Option<usize>,
+}
+
+impl ChatLog {
+    pub fn push(&mut self, line: ChatLine) {
+        if self.is_scrolled() {
+            if self.new_messages == 0 {
+                self.last_read = self.newest_id();
+            }
+            if line.kind == ChatKind::Message {
+                self.new_messages += 1;
+            }
+        }
+        if self.lines.len() >= CHAT_CAPACITY {
+            self.lines.pop_front();
+            self.dropped += 1;
+        }
+        self.lines.push_back(line);
+    }
+
+    /// Counts a message again if it's the same as the newest line, returns false otherwise
+    pub fn repeat_last(&mut self, sender: &str, text: &str) -> bool {
+        match self.lines.back_mut() {
+            Some(last)
+                if last.kind == ChatKind::Message
+                    && last.sender.as_deref() == Some(sender)
+                    && last.text == text =>
+            {
+                last.repeats += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Marks the lines so far as loaded from earlier sessions, later ones are separated from them
+    pub fn end_history(&mut self) {
+        self.history_end = self.newest_id();
+    }
+
+    /// Amount of messages which arrived since scrolling up
+    pub fn new_messages(&self) -> usize {
+        self.new_messages
+    }
+
+    pub fn lines(&self) -> impl DoubleEndedIterator<Item = &ChatLine> {
+        self.lines.iter()
+    }
+
+    /// Lines with ids in the given range, skipping dropped ones
+    pub fn lines_in(&self, ids: RangeInclusive<usize>) -> impl Iterator<Item = &ChatLine> {
+        let start = ids.start().saturating_sub(self.dropped);
+        let end = (ids.end() + 1).saturating_sub(self.dropped);
+        self.lines
+            .range(start.min(end).min(self.lines.len())..end.min(self.lines.len()))
+    }
+
+    pub fn oldest_id(&self) -> Option<usize> {
+        (!self.lines.is_empty()).then_some(self.dropped)
+    }
+
+    pub fn newest_id(&self) -> Option<usize> {
+        (self.dropped + self.lines.len()).checked_sub(1)
+    }
+
+    pub fn clear(&mut self) {
+        self.dropped += self.lines.len();
+        self.lines.clear();
+        self.scroll = 0;
+        self.new_messages = 0;
+        self.last_read = None;
+        self.history_end = None;
+    }
+
+    /// Id of the closest line older than `before` which satisfies `predicate`,
+    /// starting from the newest line if `before` is `None`
+    pub fn find_older(
+        &self,
+        before: Option<usize>,
+        predicate: impl Fn(&ChatLine) -> bool,
+    ) -> Option<usize> {
+        let end = before.map_or(self.lines.len(), |id| id.saturating_sub(self.dropped));
+        let end = end.min(self.lines.len());
+        (0..end)
+            .rev()
+            .find(|&index| predicate(&self.lines[index]))
+            .map(|index| index + self.dropped)
+    }
+
+    /// Id of the closest line newer than `after` which satisfies `predicate`
+    pub fn find_newer(&self, after: usize, predicate: impl Fn(&ChatLine) -> bool) -> Option<usize> {
+        let start = (after + 1).saturating_sub(self.dropped);
+        (start..self.lines.len())
+            .find(|&index| predicate(&self.lines[index]))
+            .map(|index| index + self.dropped)
+    }
+
+    /// Scrolls the line with the given id into view on the next render
+    pub fn jump_to(&mut self, id: usize) {
+        self.jump_to = Some(id);
+    }
+
+    pub fn scroll_up(&mut self) {
+        self.scroll = self.scroll.saturating_add(self.page_height.max(1));
+    }
+
+    pub fn scroll_down(&mut self) {
+        self.scroll_down_by(self.page_height.max(1));
+    }
+
+    pub fn is_scrolled(&self) -> bool {
+        self.scroll > 0
+    }
+
+    /// The last read marker stays in place until new lines arrive while scrolled up again
+    pub fn scroll_to_bottom(&mut self) {
+        self.scroll = 0;
+        self.new_messages = 0;
+    }
+
+    pub fn scroll_up_by(&mut self, rows: usize) {
+        self.scroll = self.scroll.saturating_add(rows);
+    }
+
+    pub fn scroll_down_by(&mut self, rows: usize) {
+        self.scroll = self.scroll.saturating_sub(rows);
+        if self.scroll == 0 {
+            self.scroll_to_bottom();
+        }
+    }
+
+    /// URL of the link drawn at a screen position
+    pub fn link_at(&self, position: Position) -> Option<&str> {
+        self.link_areas
+            .iter()
+            .find(|(area, _)| area.contains(position))
+            .map(|(_, url)| url.as_str())
+    }
+
+    /// URLs of links which are currently visible, top to bottom
+    pub fn visible_links(&self) -> Vec<String> {
+        let mut links: Vec<String> = vec![];
+        for (_, url) in &self.link_areas {
+            if !links.contains(url) {
+                links.push(url.clone());
+            }
+        }
+        links
+    }
+
+    /// Username of the sender drawn at a screen position
+    pub fn sender_at(&self, position: Position) -> Option<&str> {
+        self.sender_areas
+            .iter()
+            .find(|(area, _)| area.contains(position))
+            .map(|(_, sender)| sender.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ChatStyles {
+    pub timestamp: Style,
+    pub error: Style,
+    pub warn: Style,
+    pub info: Style,
+    pub debug: Style,
+    pub trace: Style,
+    pub mention_line: Style,
+    pub mention: Style,
+    pub last_read: Style,
+    pub search_match: Style,
+    pub search_current: Style,
+    pub link: Style,
+    pub selection: Style,
+    /// Colours picked from by username hash, empty to keep senders plain
+    pub sender_palette: Vec<Color>,
+}
+
+impl ChatStyles {
+    fn level(&self, level: Level) -> Style {
+        match level {
+            Level::Error => self.error,
+            Level::Warn => self.warn,
+            Level::Info => self.info,
+            Level::Debug => self.debug,
+            Level::Trace => self.trace,
+        }
+    }
+
+    pub fn sender(&self, sender: &str, style: Style) -> Style {
+        match self.sender_palette.len() {
+            0 => style,
+            len => style.fg(self.sender_palette[(username_hash(sender) % len as u64) as usize]),
+        }
+    }
+}
+
+/// Renders the bottom of a [`ChatLog`], wrapping long lines
+pub struct ChatView<'a> {
+    block: Option<Block<'a>>,
+    styles: &'a ChatStyles,
+    search: Option<&'a SearchQuery>,
+    current_match: Option<usize>,
+    hyperlinks: bool,
+    selection: Option<RangeInclusive<usize>>,
+}
+
+/// A row of a wrapped chat line
+struct Row<'a> {
+    line: Line<'static>,
+    /// Set on the first row of messages
+    sender: Option<&'a String>,
+    /// Columns of links and their URLs
+    links: Vec<(Range<u16>, String)>,
+}
+
+impl<'a> ChatView<'a> {
+    pub fn new(styles: &'a ChatStyles) -> Self {
+        ChatView {
+            block: None,
+            styles,
+            search: None,
+            current_match: None,
+            hyperlinks: false,
+            selection: None,
+        }
+    }
+
+    /// Makes links clickable with OSC 8 escape sequences
+    pub fn hyperlinks(mut self, enabled: bool) -> Self {
+        self.hyperlinks = enabled;
+        self
+    }
+
+    pub fn block(mut self, block: Block<'a>) -> Self {
+        self.block = Some(block);
+        self
+    }
+
+    /// Highlights search matches, the line with id `current` stands out
+    pub fn search(mut self, query: Option<&'a SearchQuery>, current: Option<usize>) -> Self {
+        self.search = query;
+        self.current_match = current;
+        self
+    }
+
+    /// Marks the lines with ids in the range as selected
+    pub fn selection(mut self, ids: Option<RangeInclusive<usize>>) -> Self {
+        self.selection = ids;
+        self
+    }
+
+    /// A styled chat line and character ranges of the URLs in it
+    fn line(
+        &self,
+        chat_line: &ChatLine,
+        id: usize,
+    ) -> (Line<'static>, Vec<(Range<usize>, String)>) {
+        let mut style = self.styles.level(chat_line.level);
+        if chat_line.is_mention() {
+            style = style.patch(self.styles.mention_line);
+        }
+
+        let mut spans = vec![Span::styled(
+            format!("{} ", chat_line.time.format("%H:%M:%S")),
+            self.styles.timestamp,
+        )];
+        if let (ChatKind::Message, Some(sender)) = (chat_line.kind, &chat_line.sender) {
+            spans.push(Span::styled(
+                format!("[{}] ", sender),
+                self.styles.sender(sender, style),
+            ));
+        }
+
+        let prefix_chars: usize = spans.iter().map(|span| span.content.chars().count()).sum();
+        let text = &chat_line.text;
+        let links = chat_line
+            .links
+            .iter()
+            .filter_map(|link| {
+                let url = sanitize_url(&text[link.clone()])?;
+                let start = prefix_chars + text[..link.start].chars().count();
+                let end = start + text[link.clone()].chars().count();
+                Some((start..end, url))
+            })
+            .collect();
+
+        let mut highlights: Vec<(Range<usize>, Style)> = chat_line
+            .links
+            .iter()
+            .map(|link| (link.clone(), self.styles.link))
+            .collect();
+        highlights.extend(
+            chat_line
+                .mentions
+                .iter()
+                .map(|mention| (mention.clone(), self.styles.mention)),
+        );
+        if let Some(query) = self.search {
+            let search_style = match self.current_match == Some(id) {
+                true => self.styles.search_current,
+                false => self.styles.search_match,
+            };
+            let found = query.find(chat_line).into_iter();
+            highlights.extend(found.map(|range| (range, search_style)));
+        }
+        spans.extend(highlight_spans(&chat_line.text, style, &highlights));
+        if chat_line.repeats > 1 {
+            spans.push(Span::styled(
+                format!(" ×{}", chat_line.repeats),
+                self.styles.timestamp,
+            ));
+        }
+        if self.selection.as_ref().is_some_and(|ids| ids.contains(&id)) {
+            for span in &mut spans {
+                span.style = span.style.patch(self.styles.selection);
+            }
+        }
+
+        (Line::from(spans), links)
+    }
+}
+
+/// Screen columns of links in every row of a wrapped line
+fn row_links(rows: &[Line], links: &[(Range<usize>, String)]) -> Vec<Vec<(Range<u16>, String)>> {
+    let mut offset = 0;
+    rows.iter()
+        .map(|row| {
+            let mut segments: Vec<(Range<u16>, String)> = vec![];
+            let mut column = 0;
+            for grapheme in row
+                .spans
+                .iter()
+                .flat_map(|span| span.content.graphemes(true))
+            {
+                let width = grapheme.width() as u16;
+                if let Some((_, url)) = links.iter().find(|(range, _)| range.contains(&offset)) {
+                    match segments.last_mut() {
+                        Some((columns, last)) if columns.end == column && last == url => {
+                            columns.end += width
+                        }
+                        _ => segments.push((column..column + width, url.clone())),
+                    }
+                }
+                column += width;
+                offset += grapheme.chars().count();
+            }
+            segments
+        })
+        .collect()
+}
+
+/// Wraps pairs of cells in OSC 8 hyperlinks.
+/// The buffer takes a symbol wider than one cell to cover the next cell too,
+/// so two characters are written at once and the second cell is skipped.
+/// A lone last character and wide characters stay plain, rather than hiding cells after them.
+fn hyperlink_cells(buf: &mut Buffer, area: Rect, url: &str) {
+    let mut x = area.left();
+    while x + 1 < area.right() {
+        let first = buf[(x, area.y)].symbol().to_string();
+        let second = buf[(x + 1, area.y)].symbol().to_string();
+        match (first.width(), second.width()) {
+            (1, 1) => {
+                buf[(x, area.y)].set_symbol(&hyperlink(url, &format!("{}{}", first, second)));
+                x += 2;
+            }
+            (2, _) => x += 2,
+            _ => x += 1,
+        }
+    }
+}
+
+/// Splits text into spans, patching the base style with every highlight covering a part
+fn highlight_spans(
+    text: &str,
+    style: Style,
+    highlights: &[(Range<usize>, Style)],
+) -> Vec<Span<'static>> {
+    let mut bounds: Vec<usize> = highlights
+        .iter()
+        .flat_map(|(range, _)| [range.start, range.end])
+        .chain([0, text.len()])
+        .collect();
+    bounds.sort_unstable();
+    bounds.dedup();
+    bounds
+        .windows(2)
+        .map(|part| {
+            let style = highlights
+                .iter()
+                .filter(|(range, _)| range.start <= part[0] && part[1] <= range.end)
+                .fold(style, |style, (_, highlight)| style.patch(*highlight));
+            Span::styled(text[part[0]..part[1]].to_string(), style)
+        })
+        .collect()
+}
+
+fn separator(width: usize, label: &str, style: Style) -> Line<'static> {
+    let left = width.saturating_sub(label.len()) / 2;
+    let right = width.saturating_sub(label.len() + left);
+    let text = format!("{}{}{}", "─".repeat(left), label, "─".repeat(right));
+    Line::styled(text.chars().take(width).collect::<String>(), style)
+}
+
+/// Splits a line into rows which are at most `width` columns wide,
+/// wide characters like emoji and CJK never get split across rows
+fn wrap_line(line: Line<'static>, width: usize) -> Vec<Line<'static>> {
+    let mut rows = vec![];
+    let mut row: Vec<Span<'static>> = vec![];
+    let mut row_width = 0;
+    for span in line.spans {
+        let mut chunk = String::new();
+        for grapheme in span.content.graphemes(true) {
+            let grapheme_width = grapheme.width();
+            if row_width + grapheme_width > width && row_width > 0 {
+                row.push(Span::styled(std::mem::take(&mut chunk), span.style));
+                rows.push(Line::from(std::mem::take(&mut row)));
+                row_width = 0;
+            }
+            chunk.push_str(grapheme);
+            row_width += grapheme_width;
+        }
+        row.push(Span::styled(chunk, span.style));
+    }
+    rows.push(Line::from(row));
+    rows
+}
+
+impl StatefulWidget for ChatView<'_> {
+    type State = ChatLog;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        let inner = match &self.block {
+            Some(block) => {
+                let inner = block.inner(area);
+                block.clone().render(area, buf);
+                inner
+            }
+            None => area,
+        };
+        let width = inner.width as usize;
+        let height = inner.height as usize;
+        state.page_height = height;
+        state.sender_areas.clear();
+        state.link_areas.clear();
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        // Wrap lines from the bottom until the visible page is filled,
+        // first rows of messages remember their sender
+        let mut rows: VecDeque<Row> = VecDeque::new();
+        let mut jump_to = state.jump_to.take();
+        // While scrolled up, rows added since the last render push the view up to keep it in place
+        let mut keep_position = state.scroll > 0 && state.rendered_until.is_some();
+        for (index, chat_line) in state.lines.iter().enumerate().rev() {
+            let id = index + state.dropped;
+            if keep_position && Some(id) <= state.rendered_until {
+                keep_position = false;
+                state.scroll += rows.len();
+            }
+            let sender = match chat_line.kind {
+                ChatKind::Message => chat_line.sender.as_ref(),
+                _ => None,
+            };
+            let (line, links) = self.line(chat_line, id);
+            let wrapped = wrap_line(line, width);
+            let links = row_links(&wrapped, &links);
+            let rows_below = rows.len();
+            let line_height = wrapped.len();
+            for (index, (line, links)) in wrapped.into_iter().zip(links).enumerate().rev() {
+                let sender = sender.filter(|_| index == 0);
+                rows.push_front(Row {
+                    line,
+                    sender,
+                    links,
+                });
+            }
+            if state.last_read.is_some_and(|last_read| last_read + 1 == id) {
+                rows.push_front(Row {
+                    line: separator(width, " new ", self.styles.last_read),
+                    sender: None,
+                    links: vec![],
+                });
+            }
+            if state
+                .history_end
+                .is_some_and(|history_end| history_end + 1 == id)
+            {
+                rows.push_front(Row {
+                    line: separator(width, " live ", self.styles.timestamp),
+                    sender: None,
+                    links: vec![],
+                });
+            }
+
+            // Center the line we're jumping to, unless it's already visible
+            if jump_to == Some(id) {
+                jump_to = None;
+                let visible = state.scroll..state.scroll + height;
+                if !visible.contains(&rows_below)
+                    || !visible.contains(&rows.len().saturating_sub(1))
+                {
+                    state.scroll = (rows_below + line_height / 2).saturating_sub(height / 2);
+                }
+            }
+            if jump_to.is_none() && !keep_position && rows.len() >= height + state.scroll {
+                break;
+            }
+        }
+        state.rendered_until = state.newest_id();
+
+        // Don't let scrolling run past the oldest line
+        state.scroll = state.scroll.min(rows.len().saturating_sub(height));
+        let visible_end = rows.len() - state.scroll;
+        let visible_start = visible_end.saturating_sub(height);
+        let mut visible = vec![];
+        let mut sender_areas = vec![];
+        let mut link_areas = vec![];
+        for (y, row) in rows.drain(visible_start..visible_end).enumerate() {
+            let y = inner.y + y as u16;
+            if let (Some(sender), [timestamp, sender_span, ..]) =
+                (row.sender, row.line.spans.as_slice())
+            {
+                let x = timestamp.width() as u16;
+                let sender_width = (sender_span.width() as u16).min(inner.width.saturating_sub(x));
+                let sender_area = Rect::new(inner.x + x, y, sender_width, 1);
+                sender_areas.push((sender_area, sender.clone()));
+            }
+            for (columns, url) in row.links {
+                let link_area = Rect::new(inner.x + columns.start, y, columns.len() as u16, 1);
+                link_areas.push((link_area.intersection(inner), url));
+            }
+            visible.push(row.line);
+        }
+        state.sender_areas = sender_areas;
+
+        Paragraph::new(visible).render(inner, buf);
+        if self.hyperlinks {
+            for (area, url) in &link_areas {
+                hyperlink_cells(buf, *area, url);
+            }
+        }
+        state.link_areas = link_areas;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::theme::Theme;
+
+    fn matched<'a>(text: &'a str, matcher: &MentionMatcher) -> Vec<&'a str> {
+        matcher
+            .find(text)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect()
+    }
+
+    #[test]
+    fn check_mention_matching() {
+        let matcher = MentionMatcher::new("johndoe", &[String::from("rust")]);
+        assert_eq!(
+            matched("hey @JohnDoe, rust?", &matcher),
+            vec!["@JohnDoe", "rust"]
+        );
+        assert_eq!(matched("johndoe2 trusty", &matcher), Vec::<&str>::new());
+        assert_eq!(matched("Ā johndoe", &matcher), vec!["johndoe"]);
+    }
+
+    #[test]
+    fn check_highlight_spans() {
+        let red = Style::default().fg(Color::Red);
+        let bold = Style::default().bold();
+        let spans = highlight_spans(
+            "hello world",
+            Style::default(),
+            &[(0..5, red), (3..8, bold)],
+        );
+        let parts: Vec<(&str, Style)> = spans
+            .iter()
+            .map(|span| (span.content.as_ref(), span.style))
+            .collect();
+        assert_eq!(
+            parts,
+            vec![
+                ("hel", red),
+                ("lo", red.patch(bold)),
+                (" wo", bold),
+                ("rld", Style::default()),
+            ]
+        );
+    }
+
+    #[test]
+    fn check_wide_wrapping() {
+        let line = Line::from(vec![Span::raw("ab中"), Span::raw("文👩‍👩‍👧e\u{301}")]);
+        let rows: Vec<String> = wrap_line(line, 3)
+            .iter()
+            .map(|row| row.spans.iter().map(|span| span.content.as_ref()).collect())
+            .collect();
+        assert_eq!(rows, vec!["ab", "中", "文", "👩‍👩‍👧e\u{301}"]);
+    }
+
+    #[test]
+    fn check_row_links() {
+        let rows = vec![
+            Line::from("see ht"),
+            Line::from("tp://a 😀"),
+            Line::from("x"),
+        ];
+        let url = String::from("http://a");
+        assert_eq!(
+            row_links(&rows, &[(4..12, url.clone())]),
+            vec![vec![(4..6, url.clone())], vec![(0..6, url)], vec![]]
+        );
+    }
+
+    fn render_rows(chat: &mut ChatLog, styles: &ChatStyles) -> Vec<String> {
+        let area = Rect::new(0, 0, 20, 3);
+        let mut buf = Buffer::empty(area);
+        ChatView::new(styles).render(area, &mut buf, chat);
+        (0..area.height)
+            .map(|y| {
+                let row: String = (0..area.width).map(|x| buf[(x, y)].symbol()).collect();
+                // Drop the timestamp
+                match row.chars().nth(2) {
+                    Some(':') => row[9..].trim_end().to_string(),
+                    _ => row,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn check_scroll_kept_on_new_lines() {
+        let styles = Theme::default().chat;
+        let mut chat = ChatLog::default();
+        for n in 0..10 {
+            chat.push(ChatLine::notice(Level::Info, n.to_string()));
+        }
+        render_rows(&mut chat, &styles);
+        chat.scroll_up_by(2);
+        assert_eq!(render_rows(&mut chat, &styles), vec!["5", "6", "7"]);
+
+        chat.push(ChatLine::message(
+            String::from("bob"),
+            String::from("hi"),
+            vec![],
+        ));
+        chat.push(ChatLine::notice(Level::Info, String::from("10")));
+        assert_eq!(render_rows(&mut chat, &styles), vec!["5", "6", "7"]);
+        assert_eq!(chat.new_messages(), 1);
+
+        chat.scroll_to_bottom();
+        assert_eq!(
+            render_rows(&mut chat, &styles),
+            vec!["─────── new ────────", "[bob] hi", "10"]
+        );
+        assert_eq!(chat.new_messages(), 0);
+    }
+
+    #[test]
+    fn check_history_separator() {
+        let styles = Theme::default().chat;
+        let mut chat = ChatLog::default();
+        chat.push(ChatLine::notice(Level::Info, String::from("earlier")));
+        chat.end_history();
+        chat.push(ChatLine::notice(Level::Info, String::from("now")));
+        assert_eq!(
+            render_rows(&mut chat, &styles),
+            vec!["earlier", "─────── live ───────", "now"]
+        );
+    }
+
+    #[test]
+    fn check_line_ids() {
+        let mut chat = ChatLog::default();
+        for text in ["a", "b", "a"] {
+            chat.push(ChatLine::notice(Level::Info, text.to_string()));
+        }
+        chat.clear();
+        for text in ["a", "b", "a"] {
+            chat.push(ChatLine::notice(Level::Info, text.to_string()));
+        }
+        let is_a = |line: &ChatLine| line.text == "a";
+        assert_eq!(chat.find_older(None, is_a), Some(5));
+        assert_eq!(chat.find_older(Some(5), is_a), Some(3));
+        assert_eq!(chat.find_older(Some(3), is_a), None);
+        assert_eq!(chat.find_newer(3, is_a), Some(5));
+    }
+}
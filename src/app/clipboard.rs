@@ -0,0 +1,25 @@
+use std::io::{self, Write};
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+
+/// Copies text into the system clipboard through the terminal with OSC 52,
+/// which works over SSH too but needs `set-clipboard on` inside tmux
+pub fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    stdout.write_all(osc52(text).as_bytes())?;
+    stdout.flush()
+}
+
+fn osc52(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", BASE64_STANDARD.encode(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_osc52() {
+        assert_eq!(osc52("hi\x1b"), "\x1b]52;c;aGkb\x07");
+    }
+}
@@ -0,0 +1,228 @@
+use log::LevelFilter;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Quit(Option<String>),
+    Clear,
+    Me(String),
+    Ignore(Option<String>),
+    Unignore(String),
+    Filter(Option<String>),
+    Unfilter(String),
+    Joins(bool),
+    Collapse(bool),
+    DoNotDisturb(Option<bool>),
+    Help,
+    Reconnect,
+    Export(Option<String>),
+    Log(LevelFilter),
+    Raw(String),
+}
+
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub description: &'static str,
+    parse: fn(Option<&str>) -> Option<Command>,
+}
+
+/// All commands which can be typed into the prompt
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "quit",
+        usage: "/quit [reason]",
+        description: "Leave the server, optionally saying why",
+        parse: |args| Some(Command::Quit(args.map(str::to_string))),
+    },
+    CommandSpec {
+        name: "clear",
+        usage: "/clear",
+        description: "Clear the chat view",
+        parse: |args| args.is_none().then_some(Command::Clear),
+    },
+    CommandSpec {
+        name: "me",
+        usage: "/me <action>",
+        description: "Describe what you're doing",
+        parse: |args| args.map(|action| Command::Me(action.to_string())),
+    },
+    CommandSpec {
+        name: "ignore",
+        usage: "/ignore [username]",
+        description: "Hide messages from a user or list ignored users",
+        parse: |args| match args {
+            Some(username) if username.contains(' ') => None,
+            _ => Some(Command::Ignore(args.map(str::to_string))),
+        },
+    },
+    CommandSpec {
+        name: "unignore",
+        usage: "/unignore <username>",
+        description: "Show messages from an ignored user again",
+        parse: |args| match args {
+            Some(username) if !username.contains(' ') => {
+                Some(Command::Unignore(username.to_string()))
+            }
+            _ => None,
+        },
+    },
+    CommandSpec {
+        name: "filter",
+        usage: "/filter [regex]",
+        description: "Hide messages matching a regex or list filters",
+        parse: |args| Some(Command::Filter(args.map(str::to_string))),
+    },
+    CommandSpec {
+        name: "unfilter",
+        usage: "/unfilter <regex>",
+        description: "Remove a message filter",
+        parse: |args| args.map(|pattern| Command::Unfilter(pattern.to_string())),
+    },
+    CommandSpec {
+        name: "joins",
+        usage: "/joins <show|hide>",
+        description: "Show or hide join and quit notices",
+        parse: |args| match args {
+            Some("show") => Some(Command::Joins(true)),
+            Some("hide") => Some(Command::Joins(false)),
+            _ => None,
+        },
+    },
+    CommandSpec {
+        name: "collapse",
+        usage: "/collapse <on|off>",
+        description: "Collapse repeated messages into a single line",
+        parse: |args| match args {
+            Some("on") => Some(Command::Collapse(true)),
+            Some("off") => Some(Command::Collapse(false)),
+            _ => None,
+        },
+    },
+    CommandSpec {
+        name: "dnd",
+        usage: "/dnd [on|off]",
+        description: "Toggle do-not-disturb, which silences notifications",
+        parse: |args| match args {
+            None => Some(Command::DoNotDisturb(None)),
+            Some("on") => Some(Command::DoNotDisturb(Some(true))),
+            Some("off") => Some(Command::DoNotDisturb(Some(false))),
+            _ => None,
+        },
+    },
+    CommandSpec {
+        name: "help",
+        usage: "/help",
+        description: "List available commands",
+        parse: |args| args.is_none().then_some(Command::Help),
+    },
+    CommandSpec {
+        name: "reconnect",
+        usage: "/reconnect",
+        description: "Connect and join the server again",
+        parse: |args| args.is_none().then_some(Command::Reconnect),
+    },
+    CommandSpec {
+        name: "export",
+        usage: "/export [file]",
+        description: "Save the chat history to a text file",
+        parse: |args| Some(Command::Export(args.map(str::to_string))),
+    },
+    CommandSpec {
+        name: "log",
+        usage: "/log <off|error|warn|info|debug|trace>",
+        description: "Change the log level",
+        parse: |args| {
+            args.and_then(|level| LevelFilter::from_str(level).ok())
+                .map(Command::Log)
+        },
+    },
+    CommandSpec {
+        name: "raw",
+        usage: "/raw <username> <TYPE> [content]",
+        description: "Send a literal DSP frame",
+        parse: |args| args.map(|frame| Command::Raw(frame.to_string())),
+    },
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PromptInput {
+    Message(String),
+    Command(Command),
+}
+
+/// Interprets a line typed into the prompt, `//` escapes a message starting with a slash
+pub fn parse_prompt(input: &str) -> Result<PromptInput, String> {
+    let Some(command_line) = input.strip_prefix('/') else {
+        return Ok(PromptInput::Message(input.to_string()));
+    };
+    if command_line.starts_with('/') {
+        return Ok(PromptInput::Message(command_line.to_string()));
+    }
+
+    let (name, args) = match command_line.split_once(' ') {
+        Some((name, args)) => (name, Some(args.trim()).filter(|args| !args.is_empty())),
+        None => (command_line, None),
+    };
+    let spec =
+        find_command(name).ok_or_else(|| format!("Unknown command '/{}', see /help", name))?;
+    (spec.parse)(args)
+        .map(PromptInput::Command)
+        .ok_or_else(|| format!("Usage: {}", spec.usage))
+}
+
+pub fn find_command(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|spec| spec.name == name)
+}
+
+/// Command names starting with `prefix`, including the leading slash
+pub fn complete_command(prefix: &str) -> Vec<String> {
+    let prefix = prefix.strip_prefix('/').unwrap_or(prefix);
+    COMMANDS
+        .iter()
+        .filter(|spec| spec.name.starts_with(prefix))
+        .map(|spec| format!("/{}", spec.name))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_prompt_parsing() {
+        assert_eq!(
+            parse_prompt("hello"),
+            Ok(PromptInput::Message(String::from("hello")))
+        );
+        assert_eq!(
+            parse_prompt("//slash"),
+            Ok(PromptInput::Message(String::from("/slash")))
+        );
+        assert_eq!(
+            parse_prompt("/quit see you later"),
+            Ok(PromptInput::Command(Command::Quit(Some(String::from(
+                "see you later"
+            )))))
+        );
+        assert_eq!(
+            parse_prompt("/log debug"),
+            Ok(PromptInput::Command(Command::Log(LevelFilter::Debug)))
+        );
+        assert_eq!(
+            parse_prompt("/me"),
+            Err(String::from("Usage: /me <action>"))
+        );
+        assert!(parse_prompt("/nope").is_err());
+    }
+
+    #[test]
+    fn check_command_completion() {
+        assert_eq!(complete_command("/r"), vec!["/reconnect", "/raw"]);
+        assert_eq!(complete_command("/q"), vec!["/quit"]);
+        assert_eq!(
+            parse_prompt("/joins maybe"),
+            Err(String::from("Usage: /joins <show|hide>"))
+        );
+    }
+}
@@ -0,0 +1,42 @@
+use super::prompt::PromptLine;
+
+/// Tab-completion of the word before the prompt's cursor, repeated completions cycle
+/// through the candidates
+pub struct Completion {
+    start: usize,
+    candidates: Vec<String>,
+    index: usize,
+}
+
+impl Completion {
+    pub fn new(start: usize, candidates: Vec<String>) -> Option<Completion> {
+        if candidates.is_empty() {
+            return None;
+        }
+        Some(Completion {
+            start,
+            candidates,
+            index: 0,
+        })
+    }
+
+    /// Replaces the word before the prompt's cursor with the current candidate
+    pub fn apply(&self, prompt: &mut PromptLine) {
+        prompt.replace_before_cursor(self.start, &self.candidates[self.index]);
+    }
+
+    pub fn next(&mut self) {
+        self.index = (self.index + 1) % self.candidates.len();
+    }
+}
+
+/// Byte offset and contents of the last whitespace-separated word
+pub fn last_word(input: &str) -> (usize, &str) {
+    let start = input
+        .char_indices()
+        .rev()
+        .find(|(_, c)| c.is_whitespace())
+        .map(|(index, c)| index + c.len_utf8())
+        .unwrap_or(0);
+    (start, &input[start..])
+}
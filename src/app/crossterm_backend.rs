@@ -1,6 +1,10 @@
 use super::event::AppEvent;
 pub use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode as Key},
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        Event, KeyCode as Key, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent,
+        MouseEventKind,
+    },
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
@@ -8,10 +12,14 @@ use log::*;
 use ratatui::prelude::*;
 use std::{io, sync::mpsc};
 
-pub fn init_terminal() -> io::Result<Terminal<impl Backend>> {
+/// Without mouse capture the terminal keeps its own text selection and copy/paste
+pub fn init_terminal(mouse_capture: bool) -> io::Result<Terminal<impl Backend>> {
     trace!(target:"crossterm", "Initializing terminal");
     enable_raw_mode()?;
-    execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(io::stdout(), EnterAlternateScreen, EnableBracketedPaste)?;
+    if mouse_capture {
+        execute!(io::stdout(), EnableMouseCapture)?;
+    }
     let backend = CrosstermBackend::new(io::stdout());
     Terminal::new(backend)
 }
@@ -19,7 +27,12 @@ pub fn init_terminal() -> io::Result<Terminal<impl Backend>> {
 pub fn restore_terminal() -> io::Result<()> {
     trace!(target:"crossterm", "Restoring terminal");
     disable_raw_mode()?;
-    execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture)
+    execute!(
+        io::stdout(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        DisableBracketedPaste
+    )
 }
 
 pub fn input_thread(tx_event: mpsc::Sender<AppEvent>) -> anyhow::Result<()> {
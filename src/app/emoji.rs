@@ -0,0 +1,94 @@
+use serde::Deserialize;
+
+/// Most shortcodes offered by a single completion
+const COMPLETION_LIMIT: usize = 30;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmojiConfig {
+    /// Replace `:shortcode:` with its emoji while typing, and complete shortcodes
+    pub shortcodes: bool,
+    /// Show shortcodes in received messages as emoji
+    pub render_incoming: bool,
+}
+
+impl Default for EmojiConfig {
+    fn default() -> Self {
+        EmojiConfig {
+            shortcodes: true,
+            render_incoming: false,
+        }
+    }
+}
+
+/// Replaces every known `:shortcode:` with its emoji, unknown ones are kept as they are
+pub fn expand_shortcodes(text: &str) -> String {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(':') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let emoji = after
+            .find(':')
+            .and_then(|end| Some((end, emojis::get_by_shortcode(&after[..end])?)));
+        match emoji {
+            Some((end, emoji)) => {
+                expanded.push_str(emoji.as_str());
+                rest = &after[end + 1..];
+            }
+            None => {
+                expanded.push(':');
+                rest = after;
+            }
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// The emoji of a `:shortcode:` which ends the text, with the shortcode's byte offset
+pub fn trailing_shortcode(text: &str) -> Option<(usize, &'static str)> {
+    let name = text.strip_suffix(':')?;
+    let start = name.rfind(':')?;
+    let emoji = emojis::get_by_shortcode(&name[start + 1..])?;
+    Some((start, emoji.as_str()))
+}
+
+/// Emoji whose shortcodes start with a word like `:thu`, sorted by shortcode
+pub fn complete_shortcode(word: &str) -> Vec<String> {
+    let Some(prefix) = word.strip_prefix(':') else {
+        return vec![];
+    };
+    if prefix.len() < 2 || prefix.contains(':') {
+        return vec![];
+    }
+    let mut found: Vec<(&str, &str)> = emojis::iter()
+        .filter_map(|emoji| {
+            let shortcode = emoji.shortcodes().find(|code| code.starts_with(prefix))?;
+            Some((shortcode, emoji.as_str()))
+        })
+        .collect();
+    found.sort();
+    found
+        .into_iter()
+        .take(COMPLETION_LIMIT)
+        .map(|(_, emoji)| emoji.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_shortcodes() {
+        assert_eq!(
+            expand_shortcodes("hi :wave: at 10:30 :nope: :+1:"),
+            "hi 👋 at 10:30 :nope: 👍"
+        );
+        assert_eq!(trailing_shortcode("ok :tada:"), Some((3, "🎉")));
+        assert_eq!(trailing_shortcode("ok :tada"), None);
+        assert_eq!(complete_shortcode(":thumbs")[0], "👎");
+        assert!(complete_shortcode(":t").is_empty());
+    }
+}
@@ -1,4 +1,7 @@
-use crate::{client::DspWriter, protocol::DspPayload};
+use crate::{
+    client::{DspClient, DspWriter},
+    protocol::DspPayload,
+};
 pub use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode as Key},
     execute,
@@ -8,7 +11,13 @@ pub use crossterm::{
 pub enum AppEvent {
     UiEvent(Event),
     PayloadReceived(DspPayload),
-    PayloadSent((DspWriter, DspPayload)),
+    /// Writer handed back with the connection generation it belongs to
+    PayloadSent((u64, DspWriter, DspPayload)),
+    /// Payload which couldn't be written, its writer is dropped
+    SendFailed((u64, DspPayload, String)),
+    Reconnected((u64, DspClient)),
+    Disconnected((u64, String)),
+    ReplayFinished(),
     FatalError(String),
     Rerender(),
 }
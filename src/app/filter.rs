@@ -0,0 +1,122 @@
+use std::collections::BTreeSet;
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterConfig {
+    /// Usernames whose messages are hidden
+    pub ignore: BTreeSet<String>,
+    /// Regexes of message contents to hide
+    pub patterns: Vec<String>,
+    pub hide_joins: bool,
+    /// Show repeats of the same message from the same user as a single line
+    pub collapse_repeats: bool,
+}
+
+/// Decides which incoming payloads reach the chat view
+#[derive(Debug, Default)]
+pub struct ChatFilter {
+    config: FilterConfig,
+    patterns: Vec<Regex>,
+}
+
+impl ChatFilter {
+    pub fn new(config: FilterConfig) -> Result<ChatFilter> {
+        let patterns = config
+            .patterns
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).with_context(|| format!("Invalid filter '{}'", pattern))
+            })
+            .collect::<Result<_>>()?;
+        Ok(ChatFilter { config, patterns })
+    }
+
+    pub fn config(&self) -> &FilterConfig {
+        &self.config
+    }
+
+    pub fn hides_joins(&self) -> bool {
+        self.config.hide_joins
+    }
+
+    pub fn collapses_repeats(&self) -> bool {
+        self.config.collapse_repeats
+    }
+
+    pub fn hides_message(&self, username: &str, text: &str) -> bool {
+        self.config.ignore.contains(username)
+            || self.patterns.iter().any(|pattern| pattern.is_match(text))
+    }
+
+    /// Returns false if the user was already ignored
+    pub fn ignore(&mut self, username: &str) -> bool {
+        self.config.ignore.insert(username.to_string())
+    }
+
+    /// Returns false if the user wasn't ignored
+    pub fn unignore(&mut self, username: &str) -> bool {
+        self.config.ignore.remove(username)
+    }
+
+    pub fn add_pattern(&mut self, pattern: &str) -> Result<(), String> {
+        if self.config.patterns.iter().any(|known| known == pattern) {
+            return Err(format!("Already filtering '{}'", pattern));
+        }
+        let regex = Regex::new(pattern).map_err(|err| format!("Invalid regex: {}", err))?;
+        self.config.patterns.push(pattern.to_string());
+        self.patterns.push(regex);
+        Ok(())
+    }
+
+    /// Returns false if there was no such filter
+    pub fn remove_pattern(&mut self, pattern: &str) -> bool {
+        let Some(index) = self.config.patterns.iter().position(|p| p == pattern) else {
+            return false;
+        };
+        self.config.patterns.remove(index);
+        self.patterns.remove(index);
+        true
+    }
+
+    pub fn set_hide_joins(&mut self, hide: bool) {
+        self.config.hide_joins = hide;
+    }
+
+    pub fn set_collapse_repeats(&mut self, collapse: bool) {
+        self.config.collapse_repeats = collapse;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_chat_filter() {
+        let config = FilterConfig {
+            patterns: vec![String::from("(?i)buy now")],
+            ..FilterConfig::default()
+        };
+        let mut filter = ChatFilter::new(config).unwrap();
+        assert!(filter.hides_message("bob", "BUY NOW!!!"));
+        assert!(!filter.hides_message("bob", "hi"));
+
+        assert!(filter.ignore("bob"));
+        assert!(filter.hides_message("bob", "hi"));
+        assert!(!filter.hides_message("alice", "hi"));
+
+        assert!(filter.add_pattern("(").is_err());
+        assert!(filter.remove_pattern("(?i)buy now"));
+        assert!(!filter.hides_message("alice", "buy now"));
+
+        let config = FilterConfig {
+            patterns: vec![String::from("[")],
+            ..FilterConfig::default()
+        };
+        assert!(ChatFilter::new(config).is_err());
+    }
+}
@@ -0,0 +1,330 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    str::FromStr,
+};
+
+use anyhow::{Result, anyhow, bail};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+use strum_macros::{Display, EnumString};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Display, EnumString, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Action {
+    /// Send the prompt on the message tab, or trigger the selected tab
+    SendMessage,
+    /// Complete a name or command, switches tabs when there's nothing to complete
+    Complete,
+    NextTab,
+    ScrollUp,
+    ScrollDown,
+    ScrollToBottom,
+    DeleteBackward,
+    ClearPrompt,
+    /// Open the search bar, n/N then jump between matches
+    Search,
+    /// List the links in view to open or copy them
+    Links,
+    /// Move a cursor over messages to copy them
+    Select,
+    /// Toggle and resize panes
+    Layout,
+    Quit,
+}
+
+impl Action {
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::SendMessage => "Trigger state",
+            Action::Complete => "Complete",
+            Action::NextTab => "Switch state",
+            Action::ScrollUp => "Scroll up",
+            Action::ScrollDown => "Scroll down",
+            Action::ScrollToBottom => "Cancel scroll",
+            Action::DeleteBackward => "Delete",
+            Action::ClearPrompt => "Clear prompt",
+            Action::Search => "Search",
+            Action::Links => "Links",
+            Action::Select => "Select",
+            Action::Layout => "Layout",
+            Action::Quit => "Quit",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyBinding {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> KeyBinding {
+        // Shift is already part of upper-case characters and back-tab
+        let modifiers = match code {
+            KeyCode::Char(_) | KeyCode::BackTab => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+        KeyBinding { code, modifiers }
+    }
+}
+
+impl From<KeyEvent> for KeyBinding {
+    fn from(key: KeyEvent) -> Self {
+        KeyBinding::new(key.code, key.modifiers)
+    }
+}
+
+impl FromStr for KeyBinding {
+    type Err = anyhow::Error;
+
+    /// Parses keys like `ctrl-f`, `alt-v`, `pageup`, `shift-tab` or `/`
+    fn from_str(s: &str) -> Result<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+        while let Some((modifier, key)) = rest.split_once('-').filter(|(_, key)| !key.is_empty()) {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "c" => KeyModifiers::CONTROL,
+                "alt" | "m" => KeyModifiers::ALT,
+                "shift" | "s" => KeyModifiers::SHIFT,
+                _ => break,
+            };
+            rest = key;
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_lowercase().as_str() {
+                "enter" | "return" => KeyCode::Enter,
+                "esc" | "escape" => KeyCode::Esc,
+                "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" => KeyCode::Insert,
+                "space" => KeyCode::Char(' '),
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                key => match key.strip_prefix('f').map(u8::from_str) {
+                    Some(Ok(n)) if (1..=12).contains(&n) => KeyCode::F(n),
+                    _ => bail!("Unknown key '{}'", s),
+                },
+            },
+        };
+        Ok(KeyBinding::new(code, modifiers))
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "Shift-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) if self.modifiers.is_empty() => write!(f, "{}", c),
+            KeyCode::Char(c) => write!(f, "{}", c.to_ascii_uppercase()),
+            KeyCode::BackTab => write!(f, "Shift-Tab"),
+            KeyCode::PageUp => write!(f, "PageUp"),
+            KeyCode::PageDown => write!(f, "PageDown"),
+            code => write!(f, "{}", code),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeymapPreset {
+    #[default]
+    Default,
+    Vi,
+    Emacs,
+}
+
+impl KeymapPreset {
+    fn bindings(&self) -> Vec<(Action, &'static [&'static str])> {
+        type Keys = &'static [&'static str];
+        let (scroll_up, scroll_down, scroll_to_bottom, clear_prompt, search, select): (
+            Keys,
+            Keys,
+            Keys,
+            Keys,
+            Keys,
+            Keys,
+        ) = match self {
+            KeymapPreset::Default => (
+                &["pageup"],
+                &["pagedown"],
+                &["esc"],
+                &["ctrl-u"],
+                &["ctrl-f"],
+                &["ctrl-y"],
+            ),
+            KeymapPreset::Vi => (
+                &["ctrl-b", "ctrl-u", "pageup"],
+                &["ctrl-f", "ctrl-d", "pagedown"],
+                &["esc", "ctrl-g"],
+                &["ctrl-w"],
+                &["ctrl-s"],
+                &["ctrl-v"],
+            ),
+            KeymapPreset::Emacs => (
+                &["alt-v", "pageup"],
+                &["ctrl-v", "pagedown"],
+                &["ctrl-g", "esc"],
+                &["ctrl-k"],
+                &["ctrl-s", "ctrl-f"],
+                &["alt-w"],
+            ),
+        };
+        vec![
+            (Action::SendMessage, &["enter"]),
+            (Action::Complete, &["tab"]),
+            (Action::NextTab, &["shift-tab"]),
+            (Action::ScrollUp, scroll_up),
+            (Action::ScrollDown, scroll_down),
+            (Action::ScrollToBottom, scroll_to_bottom),
+            (Action::DeleteBackward, &["backspace"]),
+            (Action::ClearPrompt, clear_prompt),
+            (Action::Search, search),
+            (Action::Links, &["ctrl-o"]),
+            (Action::Select, select),
+            (Action::Layout, &["f2"]),
+            (Action::Quit, &["ctrl-c"]),
+        ]
+    }
+}
+
+/// Actions bound to keys, built from a preset with user overrides on top
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: BTreeMap<Action, Vec<KeyBinding>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::new(KeymapPreset::Default, &BTreeMap::new())
+            .expect("Default keymap must not have conflicts")
+    }
+}
+
+impl Keymap {
+    /// Overrides replace all keys of an action, a key bound to several actions is an error
+    pub fn new(preset: KeymapPreset, overrides: &BTreeMap<Action, Vec<String>>) -> Result<Keymap> {
+        let mut bindings: BTreeMap<Action, Vec<KeyBinding>> = BTreeMap::new();
+        for (action, keys) in preset.bindings() {
+            let keys = keys.iter().map(|key| KeyBinding::from_str(key));
+            bindings.insert(action, keys.collect::<Result<_>>()?);
+        }
+        for (action, keys) in overrides {
+            let keys = keys.iter().map(|key| KeyBinding::from_str(key));
+            bindings.insert(*action, keys.collect::<Result<_>>()?);
+        }
+
+        let mut bound: HashMap<KeyBinding, Action> = HashMap::new();
+        let mut conflicts = vec![];
+        for (action, keys) in &bindings {
+            for key in keys {
+                if let Some(other) = bound.insert(*key, *action) {
+                    conflicts.push(format!("'{}' is bound to {} and {}", key, other, action));
+                }
+            }
+        }
+        if !conflicts.is_empty() {
+            return Err(anyhow!(
+                "Conflicting key bindings: {}",
+                conflicts.join(", ")
+            ));
+        }
+
+        Ok(Keymap { bindings })
+    }
+
+    pub fn action(&self, key: KeyEvent) -> Option<Action> {
+        let key = KeyBinding::from(key);
+        self.bindings
+            .iter()
+            .find(|(_, keys)| keys.contains(&key))
+            .map(|(action, _)| *action)
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyBinding] {
+        self.bindings.get(&action).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Short descriptions of the main actions for the help bar
+    pub fn help(&self) -> Vec<String> {
+        [
+            (Action::Complete, "Complete / Switch state"),
+            (Action::SendMessage, Action::SendMessage.description()),
+            (Action::ScrollUp, "Scroll"),
+            (Action::ScrollToBottom, Action::ScrollToBottom.description()),
+            (Action::Search, Action::Search.description()),
+            (Action::Links, Action::Links.description()),
+            (Action::Select, Action::Select.description()),
+            (Action::Quit, Action::Quit.description()),
+        ]
+        .into_iter()
+        .filter_map(|(action, description)| {
+            let mut keys = self.keys(action).iter().map(KeyBinding::to_string);
+            let mut key = keys.next()?;
+            if action == Action::ScrollUp {
+                let down = self.keys(Action::ScrollDown).first()?;
+                key = format!("{}/{}", key, down);
+            }
+            Some(format!("{}: {}", key, description))
+        })
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_key_parsing() {
+        let parse = |key: &str| KeyBinding::from_str(key).unwrap();
+        assert_eq!(
+            parse("ctrl-f"),
+            KeyBinding::new(KeyCode::Char('f'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(parse("shift-tab"), parse("backtab"));
+        assert_eq!(
+            parse("-"),
+            KeyBinding::new(KeyCode::Char('-'), KeyModifiers::NONE)
+        );
+        assert_eq!(parse("alt--").code, KeyCode::Char('-'));
+        assert_eq!(parse("F5").code, KeyCode::F(5));
+        assert!(KeyBinding::from_str("hyper-x").is_err());
+    }
+
+    #[test]
+    fn check_keymap_conflicts() {
+        let overrides = BTreeMap::from([(Action::NextTab, vec![String::from("pageup")])]);
+        assert!(Keymap::new(KeymapPreset::Default, &overrides).is_err());
+
+        let overrides = BTreeMap::from([(Action::NextTab, vec![String::from("ctrl-n")])]);
+        let keymap = Keymap::new(KeymapPreset::Emacs, &overrides).unwrap();
+        assert_eq!(
+            keymap.keys(Action::NextTab),
+            &[KeyBinding::from_str("ctrl-n").unwrap()]
+        );
+    }
+}
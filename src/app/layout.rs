@@ -0,0 +1,161 @@
+use ratatui::layout::{Constraint, Layout, Rect};
+use serde::Deserialize;
+
+/// Terminals smaller than this always use the compact layout
+const COMPACT_WIDTH: u16 = 60;
+const COMPACT_HEIGHT: u16 = 20;
+
+const ROSTER_WIDTHS: (u16, u16) = (10, 60);
+const LOGS_HEIGHTS: (u16, u16) = (3, 30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pane {
+    Tabs,
+    Help,
+    Roster,
+    Logs,
+}
+
+/// Which panes are shown and how big they are, saved in the config file
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayoutConfig {
+    pub tabs: bool,
+    pub help: bool,
+    /// Users on the server, right of the chat
+    pub roster: bool,
+    /// Application logs, below the chat
+    pub logs: bool,
+    pub roster_width: u16,
+    pub logs_height: u16,
+    /// Always use the compact layout, not only in small terminals
+    pub compact: bool,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        LayoutConfig {
+            tabs: true,
+            help: true,
+            roster: false,
+            logs: false,
+            roster_width: 20,
+            logs_height: 8,
+            compact: false,
+        }
+    }
+}
+
+/// Where each pane goes, hidden panes have no area
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PaneAreas {
+    pub compact: bool,
+    pub tabs: Option<Rect>,
+    pub chat: Rect,
+    pub roster: Option<Rect>,
+    pub logs: Option<Rect>,
+    pub status: Rect,
+    pub prompt: Rect,
+    pub help: Option<Rect>,
+}
+
+impl LayoutConfig {
+    pub fn toggle(&mut self, pane: Pane) {
+        let shown = match pane {
+            Pane::Tabs => &mut self.tabs,
+            Pane::Help => &mut self.help,
+            Pane::Roster => &mut self.roster,
+            Pane::Logs => &mut self.logs,
+        };
+        *shown = !*shown;
+    }
+
+    pub fn resize_roster(&mut self, delta: i16) {
+        let (min, max) = ROSTER_WIDTHS;
+        self.roster_width = self
+            .roster_width
+            .saturating_add_signed(delta)
+            .clamp(min, max);
+    }
+
+    pub fn resize_logs(&mut self, delta: i16) {
+        let (min, max) = LOGS_HEIGHTS;
+        self.logs_height = self
+            .logs_height
+            .saturating_add_signed(delta)
+            .clamp(min, max);
+    }
+
+    /// Splits the screen, the compact layout drops every optional pane and most borders
+    pub fn areas(&self, area: Rect) -> PaneAreas {
+        let compact = self.compact || area.width < COMPACT_WIDTH || area.height < COMPACT_HEIGHT;
+        if compact {
+            let [chat, status, prompt] = Layout::vertical([
+                Constraint::Fill(1),
+                Constraint::Length(1),
+                Constraint::Length(2),
+            ])
+            .areas(area);
+            return PaneAreas {
+                compact,
+                chat,
+                status,
+                prompt,
+                ..PaneAreas::default()
+            };
+        }
+
+        let optional =
+            |shown: bool, length: u16| Constraint::Length(if shown { length } else { 0 });
+        let [tabs, middle, logs, status, prompt, help] = Layout::vertical([
+            optional(self.tabs, 3),
+            Constraint::Fill(1),
+            optional(self.logs, self.logs_height),
+            Constraint::Length(1),
+            Constraint::Length(3),
+            optional(self.help, 3),
+        ])
+        .areas(area);
+        let [chat, roster] = Layout::horizontal([
+            Constraint::Fill(1),
+            optional(self.roster, self.roster_width),
+        ])
+        .areas(middle);
+        PaneAreas {
+            compact,
+            tabs: self.tabs.then_some(tabs),
+            chat,
+            roster: self.roster.then_some(roster),
+            logs: self.logs.then_some(logs),
+            status,
+            prompt,
+            help: self.help.then_some(help),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_pane_areas() {
+        let mut layout = LayoutConfig::default();
+        layout.toggle(Pane::Roster);
+        layout.toggle(Pane::Help);
+        layout.resize_roster(-100);
+        let areas = layout.areas(Rect::new(0, 0, 80, 30));
+        assert!(!areas.compact);
+        assert_eq!(areas.tabs, Some(Rect::new(0, 0, 80, 3)));
+        assert_eq!(areas.chat, Rect::new(0, 3, 70, 23));
+        assert_eq!(areas.roster, Some(Rect::new(70, 3, 10, 23)));
+        assert_eq!(areas.help, None);
+        assert_eq!(areas.prompt, Rect::new(0, 27, 80, 3));
+
+        let areas = layout.areas(Rect::new(0, 0, 50, 30));
+        assert!(areas.compact);
+        assert_eq!(areas.roster, None);
+        assert_eq!(areas.chat, Rect::new(0, 0, 50, 27));
+        assert_eq!(areas.prompt, Rect::new(0, 28, 50, 2));
+    }
+}
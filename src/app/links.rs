@@ -0,0 +1,160 @@
+use std::{
+    ops::Range,
+    process::{Command, Stdio},
+    sync::LazyLock,
+    thread,
+};
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+
+static URL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)\bhttps?://[^\s\p{Cc}<>"'`]+"#).expect("URL regex must be valid")
+});
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LinksConfig {
+    /// Make links clickable with OSC 8, for terminals which support it
+    pub hyperlinks: bool,
+    /// Program and arguments run with the URL appended, links are copied when empty
+    pub opener: Vec<String>,
+}
+
+impl Default for LinksConfig {
+    fn default() -> Self {
+        LinksConfig {
+            hyperlinks: true,
+            opener: vec![],
+        }
+    }
+}
+
+/// Byte ranges of URLs in a message, without trailing punctuation
+pub fn find_links(text: &str) -> Vec<Range<usize>> {
+    URL.find_iter(text)
+        .map(|found| {
+            let url = found.as_str();
+            let opened = url.matches('(').count();
+            let mut end = url.len();
+            // Trailing punctuation usually belongs to the sentence, unless it closes a parenthesis
+            while let Some(last) = url[..end].chars().next_back() {
+                let unbalanced = last == ')' && url[..end].matches(')').count() > opened;
+                if !(".,;:!?'\"]}".contains(last) || unbalanced) {
+                    break;
+                }
+                end -= last.len_utf8();
+            }
+            found.start()..found.start() + end
+        })
+        .filter(|range| sanitize_url(&text[range.clone()]).is_some())
+        .collect()
+}
+
+/// Percent-encodes non-ASCII characters, URLs with control characters are rejected
+/// so they can't end an escape sequence early and smuggle in another one
+pub fn sanitize_url(url: &str) -> Option<String> {
+    let lower = url.to_ascii_lowercase();
+    if !(lower.starts_with("http://") || lower.starts_with("https://")) {
+        return None;
+    }
+    if url.chars().any(char::is_control) {
+        return None;
+    }
+    let mut sanitized = String::with_capacity(url.len());
+    for c in url.chars() {
+        match c {
+            '!'..='~' => sanitized.push(c),
+            _ => {
+                let mut bytes = [0; 4];
+                for byte in c.encode_utf8(&mut bytes).bytes() {
+                    sanitized.push_str(&format!("%{:02X}", byte));
+                }
+            }
+        }
+    }
+    Some(sanitized)
+}
+
+/// Wraps text in an OSC 8 hyperlink, `url` has to be sanitized
+pub fn hyperlink(url: &str, text: &str) -> String {
+    format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, text)
+}
+
+/// Popup listing the links in view, which replaces key handling while open
+#[derive(Debug)]
+pub struct LinkPicker {
+    pub links: Vec<String>,
+    pub selected: usize,
+}
+
+impl LinkPicker {
+    pub fn new(links: Vec<String>) -> LinkPicker {
+        LinkPicker { links, selected: 0 }
+    }
+
+    pub fn selected_link(&self) -> Option<&str> {
+        self.links.get(self.selected).map(String::as_str)
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1).min(self.links.len().saturating_sub(1));
+    }
+}
+
+/// Runs the configured opener without a shell, so the URL is a single argument
+pub fn open_link(opener: &[String], url: &str) -> Result<()> {
+    let Some((program, args)) = opener.split_first() else {
+        return Ok(());
+    };
+    let mut child = Command::new(program)
+        .args(args)
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to run link opener '{}'", program))?;
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn links(text: &str) -> Vec<&str> {
+        find_links(text)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect()
+    }
+
+    #[test]
+    fn check_link_detection() {
+        assert_eq!(
+            links("see https://example.com/a?b=c, and (http://x.org/wiki/Rust_(lang)))."),
+            vec!["https://example.com/a?b=c", "http://x.org/wiki/Rust_(lang)"]
+        );
+        assert_eq!(links("https://evil.com\x1b]8;;x"), vec!["https://evil.com"]);
+        assert_eq!(
+            links("ftp://nope.org javascript:alert(1)"),
+            Vec::<&str>::new()
+        );
+    }
+
+    #[test]
+    fn check_url_sanitizing() {
+        assert_eq!(
+            sanitize_url("https://example.com/ä"),
+            Some(String::from("https://example.com/%C3%A4"))
+        );
+        assert_eq!(sanitize_url("https://example.com/\x07"), None);
+        assert_eq!(sanitize_url("file:///etc/passwd"), None);
+    }
+}
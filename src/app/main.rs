@@ -1,12 +1,15 @@
-use super::{event::AppEvent, mode::AppMode, state::AppState};
+use super::{
+    chat::ChatView, event::AppEvent, links::LinkPicker, mode::AppMode, replay::Replay,
+    selection::Selection, state::AppState, status::StatusBar, theme::Theme,
+};
 use crate::app::crossterm_backend::*;
 use crate::{
     client::{DspReader, DspWriter},
-    config::DspClientConfig,
+    config::{DspClientConfig, DspUiConfig},
 };
 use ratatui::{prelude::*, widgets::*};
 use std::sync::mpsc::{self};
-use tui_logger::*;
+use tui_logger::{TuiLoggerLevelOutput, TuiLoggerWidget};
 
 pub struct App {
     state: AppState,
@@ -17,13 +20,20 @@ impl App {
         client_reader: DspReader,
         client_writer: DspWriter,
         client_config: DspClientConfig,
+        ui_config: DspUiConfig,
     ) -> App {
-        let state = AppState::new(client_reader, client_writer, client_config);
+        let state = AppState::new(client_reader, client_writer, client_config, ui_config);
+        App { state }
+    }
+
+    /// Plays a recorded session back without connecting
+    pub fn replay(replay: Replay, client_config: DspClientConfig, ui_config: DspUiConfig) -> App {
+        let state = AppState::replay(replay, client_config, ui_config);
         App { state }
     }
 
     pub fn start_with_crossterm(self) -> anyhow::Result<()> {
-        let mut terminal = init_terminal()?;
+        let mut terminal = init_terminal(self.state.ui_config.mouse_capture)?;
         terminal.clear()?;
         terminal.hide_cursor()?;
 
@@ -36,6 +46,8 @@ impl App {
     }
 
     fn start(mut self, terminal: &mut Terminal<impl Backend>) -> anyhow::Result<()> {
+        let size = terminal.size()?;
+        self.state.resize(size.width, size.height);
         let event_rx = self.state.start()?;
         self.run(terminal, event_rx)
     }
@@ -47,7 +59,7 @@ impl App {
         rx: mpsc::Receiver<AppEvent>,
     ) -> anyhow::Result<()> {
         for event in rx {
-            self.state.handle_app_event(event);
+            self.handle_app_event(event);
             if self.state.mode == AppMode::Quit {
                 break;
             }
@@ -56,9 +68,17 @@ impl App {
         Ok(())
     }
 
-    fn draw(&mut self, terminal: &mut Terminal<impl Backend>) -> anyhow::Result<()> {
+    /// Handles a single event like the main loop does, so tests can script them
+    pub fn handle_app_event(&mut self, event: AppEvent) {
+        self.state.handle_app_event(event);
+    }
+
+    pub fn draw(&mut self, terminal: &mut Terminal<impl Backend>) -> anyhow::Result<()> {
         terminal.draw(|frame| {
-            frame.render_widget(self, frame.area());
+            frame.render_widget(&mut *self, frame.area());
+            if let Some(cursor) = self.state.areas.cursor {
+                frame.set_cursor_position(cursor);
+            }
         })?;
         Ok(())
     }
@@ -66,66 +86,283 @@ impl App {
 
 impl Widget for &mut App {
     fn render(self, area: Rect, buf: &mut Buffer) {
-        let [tabs_area, smart_area, prompt_area, help_area] = Layout::vertical([
-            Constraint::Length(3),
-            Constraint::Fill(50),
-            Constraint::Length(3),
-            Constraint::Length(3),
-        ])
-        .areas(area);
-
-        Tabs::new(self.state.tab_names.iter().cloned())
-            .block(Block::default().title("States").borders(Borders::ALL))
-            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
-            .select(self.state.selected_tab)
-            .render(tabs_area, buf);
-
-        TuiLoggerWidget::default()
-            .block(
-                Block::default()
-                    .title(format!(
-                        "Server chat: {}",
-                        self.state.client_config.server_address
-                    ))
-                    .borders(Borders::ALL),
+        let panes = self.state.ui_config.layout.areas(area);
+        let smart_area = panes.chat;
+        let prompt_area = panes.prompt;
+        // The compact layout only keeps the top border for titles
+        let borders = match panes.compact {
+            true => Borders::TOP,
+            false => Borders::ALL,
+        };
+
+        let theme = &self.state.ui_config.theme;
+
+        let mentions_badge = (self.state.unread_mentions > 0).then(|| {
+            Line::from(format!("@ {} unread mentions", self.state.unread_mentions))
+                .style(theme.mention_badge)
+                .right_aligned()
+        });
+
+        self.state.areas.tabs = vec![];
+        self.state.areas.prompt = prompt_area;
+        let mut mentions_badge = match panes.tabs {
+            Some(tabs_area) => {
+                let mut tabs_block = Block::default()
+                    .title("States")
+                    .borders(Borders::ALL)
+                    .border_style(theme.border);
+                if let Some(badge) = mentions_badge {
+                    tabs_block = tabs_block.title(badge);
+                }
+                self.state.areas.tabs =
+                    tab_areas(tabs_block.inner(tabs_area), &self.state.tab_names);
+                Tabs::new(self.state.tab_names.iter().cloned())
+                    .block(tabs_block)
+                    .highlight_style(theme.selected_tab)
+                    .select(self.state.selected_tab)
+                    .render(tabs_area, buf);
+                None
+            }
+            None => mentions_badge,
+        };
+
+        let chat_title = match self.state.replaying {
+            true => String::from("Replayed chat"),
+            false => format!("Server chat: {}", self.state.client_config.server_address),
+        };
+        let mut chat_block = Block::default()
+            .title(chat_title)
+            .borders(borders)
+            .border_style(theme.border);
+        if let Some(badge) = mentions_badge.take() {
+            chat_block = chat_block.title(badge);
+        }
+        self.state.areas.new_messages = Rect::default();
+        if self.state.chat.new_messages() > 0 {
+            let badge = Line::from(format!(
+                " {} new messages ↓ ",
+                self.state.chat.new_messages()
+            ))
+            .style(theme.mention_badge)
+            .right_aligned();
+            let badge_width = (badge.width() as u16).min(smart_area.width.saturating_sub(2));
+            self.state.areas.new_messages = Rect::new(
+                smart_area.right().saturating_sub(badge_width + 1),
+                smart_area.bottom().saturating_sub(1),
+                badge_width,
+                1,
+            );
+            chat_block = chat_block.title_bottom(badge);
+        }
+
+        let search = self.state.search.as_ref();
+        ChatView::new(&theme.chat)
+            .search(
+                search.and_then(|search| search.query.as_ref()),
+                search.and_then(|search| search.current),
             )
-            .style_error(Style::default().fg(Color::Red).italic())
-            .style_debug(Style::default().fg(Color::Gray).italic())
-            .style_warn(Style::default().fg(Color::Yellow).italic())
-            .style_trace(Style::default().fg(Color::Gray).italic())
-            .style_info(Style::default().fg(Color::White))
-            .output_separator(' ')
-            .output_timestamp(Some("%H:%M:%S".to_string()))
-            .output_level(Some(TuiLoggerLevelOutput::Abbreviated))
-            .output_target(true)
-            .output_file(false)
-            .output_line(false)
-            .state(&self.state.log_state)
-            .render(smart_area, buf);
-
-        let prompt_block = Block::new()
+            .selection(self.state.selection.as_ref().map(Selection::range))
+            .hyperlinks(self.state.ui_config.links.hyperlinks)
+            .block(chat_block)
+            .render(smart_area, buf, &mut self.state.chat);
+
+        if let Some(picker) = &self.state.link_picker {
+            render_link_picker(picker, smart_area, theme, buf);
+        }
+
+        if let Some(roster_area) = panes.roster {
+            let users: Vec<ListItem> = self
+                .state
+                .roster
+                .users()
+                .map(|user| {
+                    let style = theme.chat.sender(user, theme.chat.info);
+                    ListItem::new(Span::styled(user.clone(), style))
+                })
+                .collect();
+            let title = format!("Users ({})", users.len());
+            let roster =
+                List::new(users).block(Block::bordered().title(title).border_style(theme.border));
+            Widget::render(roster, roster_area, buf);
+        }
+
+        if let Some(logs_area) = panes.logs {
+            TuiLoggerWidget::default()
+                .block(Block::bordered().title("Logs").border_style(theme.border))
+                .output_separator(' ')
+                .output_timestamp(Some(String::from("%H:%M:%S")))
+                .output_level(Some(TuiLoggerLevelOutput::Abbreviated))
+                .output_target(true)
+                .output_file(false)
+                .output_line(false)
+                .style_error(theme.chat.error)
+                .style_warn(theme.chat.warn)
+                .style_info(theme.chat.info)
+                .style_debug(theme.chat.debug)
+                .style_trace(theme.chat.trace)
+                .render(logs_area, buf);
+        }
+
+        StatusBar::new(&self.state.status)
+            .style(theme.status)
+            .error_style(theme.status_error)
+            .render(panes.status, buf);
+
+        let mut prompt_block = Block::new()
             .border_type(BorderType::Rounded)
-            .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::White))
-            .style(Style::default())
-            .title(format!("[{}]", self.state.client_config.username));
+            .borders(borders)
+            .border_style(theme.prompt_border)
+            .style(Style::default());
+        prompt_block = match (&self.state.search, &self.state.selection) {
+            (Some(_), _) => prompt_block.title("[search]"),
+            (None, Some(_)) => prompt_block.title("[select]").title_bottom(
+                Line::from("j/k: Move | v: Range | y: Copy | t: Copy text | Esc: Close")
+                    .style(theme.help),
+            ),
+            (None, None) if self.state.layout_mode => prompt_block.title("[layout]").title_bottom(
+                Line::from(
+                    "t/h/r/l: Tabs/Help/Users/Logs | c: Compact | Arrows: Resize | Esc: Done",
+                )
+                .style(theme.help),
+            ),
+            (None, None) => prompt_block.title(format!("[{}]", self.state.client_config.username)),
+        };
+        if let Some(error) = &self.state.prompt_error {
+            prompt_block =
+                prompt_block.title_bottom(Line::from(error.as_str()).style(theme.prompt_error));
+        }
+        if let Some(search) = &self.state.search {
+            let status = match (&search.error, search.editing) {
+                (Some(error), _) => Line::from(error.as_str()).style(theme.prompt_error),
+                (None, true) => {
+                    Line::from("Enter: Search | from:<user> kind:<kind> /regex/").style(theme.help)
+                }
+                (None, false) => Line::from(format!(
+                    "{}/{} | n/N: Older/Newer | /: Edit | Esc: Close",
+                    search.position, search.match_count
+                ))
+                .style(theme.help),
+            };
+            prompt_block = prompt_block.title_bottom(status);
+        }
 
         let prompt_block_inner = prompt_block.inner(prompt_area);
         prompt_block.render(prompt_area, buf);
 
-        let style = Style::new().white();
-        Text::raw(self.state.active_message.clone())
-            .style(style)
-            .render(prompt_block_inner, buf);
-
-        if area.width > 40 {
-            Text::from(vec![
-                "Tab: Switch state | Enter: Trigger state".into(),
-                "PageUp/Down: Scroll | Esc: Cancel scroll".into(),
-            ])
-            .style(Color::Gray)
-            .centered()
-            .render(help_area, buf);
+        self.state.areas.cursor = None;
+        let mut cursor = None;
+        match (&self.state.pending_paste, &self.state.search) {
+            (Some(lines), _) => Line::from(format!(
+                "Send {} pasted lines as separate messages? [y/n]",
+                lines.len()
+            ))
+            .style(theme.prompt_error)
+            .render(prompt_block_inner, buf),
+            (None, Some(search)) => {
+                Text::raw(search.input.clone())
+                    .style(theme.prompt_text)
+                    .render(prompt_block_inner, buf);
+                if search.editing {
+                    cursor = Some(Line::from(search.input.as_str()).width());
+                }
+            }
+            (None, None) => {
+                let (visible, column) = self
+                    .state
+                    .active_message
+                    .view(prompt_block_inner.width as usize);
+                Line::raw(visible.to_string())
+                    .style(theme.prompt_text)
+                    .render(prompt_block_inner, buf);
+                let typing = self.state.selected_tab == 0
+                    && self.state.link_picker.is_none()
+                    && self.state.selection.is_none();
+                if typing {
+                    cursor = Some(column);
+                }
+            }
+        }
+        if let Some(column) = cursor.filter(|_| !prompt_block_inner.is_empty()) {
+            let x = prompt_block_inner.x + (column as u16).min(prompt_block_inner.width - 1);
+            self.state.areas.cursor = Some(Position::new(x, prompt_block_inner.y));
+        }
+
+        if let Some(help_area) = panes.help {
+            let mut help = self.state.ui_config.keymap.help();
+            help.push(String::from("/help: Commands"));
+            Paragraph::new(help.join(" | "))
+                .style(theme.help)
+                .centered()
+                .wrap(Wrap { trim: true })
+                .render(help_area, buf);
+        }
+    }
+}
+
+/// Numbered list of links centred over the chat
+fn render_link_picker(picker: &LinkPicker, area: Rect, theme: &Theme, buf: &mut Buffer) {
+    let items: Vec<ListItem> = picker
+        .links
+        .iter()
+        .enumerate()
+        .map(|(index, url)| match index {
+            0..9 => ListItem::new(format!("{} {}", index + 1, url)),
+            _ => ListItem::new(format!("  {}", url)),
+        })
+        .collect();
+    let height = (items.len() as u16 + 2).min(area.height);
+    let [popup] = Layout::vertical([Constraint::Length(height)])
+        .flex(layout::Flex::Center)
+        .areas(area.inner(Margin::new(4, 0)));
+
+    let block = Block::bordered()
+        .title("Links")
+        .title_bottom(Line::from("Enter: Open | y: Copy | Esc: Close").style(theme.help))
+        .border_type(BorderType::Rounded)
+        .border_style(theme.prompt_border);
+    let mut list_state = ListState::default().with_selected(Some(picker.selected));
+    Clear.render(popup, buf);
+    StatefulWidget::render(
+        List::new(items)
+            .block(block)
+            .highlight_style(theme.selected_tab),
+        popup,
+        buf,
+        &mut list_state,
+    );
+}
+
+/// Where each title is drawn by the `Tabs` widget with its default padding and divider
+fn tab_areas(area: Rect, titles: &[&str]) -> Vec<Rect> {
+    let mut x = area.x;
+    let mut areas = vec![];
+    for title in titles {
+        let width = Line::from(*title).width() as u16 + 2;
+        areas.push(Rect::new(x, area.y, width, 1).intersection(area));
+        x = x.saturating_add(width + 1);
+    }
+    areas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_tab_areas() {
+        let area = Rect::new(1, 1, 14, 1);
+        let titles = ["Message", "Quit"];
+        let mut buf = Buffer::empty(Rect::new(0, 0, 16, 3));
+        Tabs::new(titles).render(area, &mut buf);
+
+        let areas = tab_areas(area, &titles);
+        assert_eq!(areas, [Rect::new(1, 1, 9, 1), Rect::new(11, 1, 4, 1)]);
+        // Each area covers its padded title as drawn, the last one is cut off at the edge
+        for (rect, title) in areas.iter().zip(titles) {
+            let text: String = (rect.x..rect.right())
+                .map(|x| buf[(x, rect.y)].symbol())
+                .collect();
+            assert!(format!(" {} ", title).starts_with(&text));
         }
     }
 }
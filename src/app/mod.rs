@@ -1,5 +1,22 @@
+pub mod chat;
+pub mod clipboard;
+pub mod command;
+pub mod completion;
 pub mod crossterm_backend;
+pub mod emoji;
 pub mod event;
+pub mod filter;
+pub mod keymap;
+pub mod layout;
+pub mod links;
 pub mod main;
 pub mod mode;
+pub mod notify;
+pub mod prompt;
+pub mod replay;
+pub mod roster;
+pub mod search;
+pub mod selection;
 pub mod state;
+pub mod status;
+pub mod theme;
@@ -0,0 +1,305 @@
+use std::{
+    env,
+    io::{self, Write},
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// Longest notification body, longer messages are cut off
+const BODY_LENGTH: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyTrigger {
+    /// A message mentioning us or a highlight word
+    Mention,
+    /// Any message from someone else
+    Message,
+    /// Server errors and lost connections
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyOutput {
+    Bell,
+    /// Desktop notification of iTerm2, Windows Terminal, kitty and others
+    Osc9,
+    /// Desktop notification of urxvt, foot, WezTerm and others
+    Osc777,
+    /// Marks the terminal window title until a key is pressed
+    Title,
+    Command,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifyConfig {
+    pub triggers: Vec<NotifyTrigger>,
+    /// Every message from these users notifies
+    pub users: Vec<String>,
+    pub outputs: Vec<NotifyOutput>,
+    /// Program and arguments run with the title and body appended, for the `command` output
+    pub command: Vec<String>,
+    /// Minimum seconds between two notifications
+    pub interval: u64,
+    pub do_not_disturb: bool,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        NotifyConfig {
+            triggers: vec![NotifyTrigger::Mention],
+            users: vec![],
+            outputs: vec![NotifyOutput::Bell, NotifyOutput::Title],
+            command: vec![],
+            interval: 10,
+            do_not_disturb: false,
+        }
+    }
+}
+
+/// Something which happened in the chat, which might be worth a notification
+pub enum NotifyEvent<'a> {
+    Message {
+        sender: &'a str,
+        text: &'a str,
+        mention: bool,
+    },
+    Error(&'a str),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub title: String,
+    pub body: String,
+}
+
+/// Turns chat events into notifications through the configured outputs
+pub struct Notifier {
+    config: NotifyConfig,
+    last_sent: Option<Instant>,
+    /// Notifications dropped by rate limiting since the last one sent
+    suppressed: usize,
+    title_marked: bool,
+    in_tmux: bool,
+}
+
+impl Notifier {
+    pub fn new(config: NotifyConfig) -> Notifier {
+        Notifier {
+            config,
+            last_sent: None,
+            suppressed: 0,
+            title_marked: false,
+            in_tmux: env::var_os("TMUX").is_some(),
+        }
+    }
+
+    pub fn do_not_disturb(&self) -> bool {
+        self.config.do_not_disturb
+    }
+
+    pub fn set_do_not_disturb(&mut self, enabled: bool) {
+        self.config.do_not_disturb = enabled;
+    }
+
+    /// The notification for an event, if any trigger matches it
+    pub fn notification(&self, event: &NotifyEvent) -> Option<Notification> {
+        let triggers = &self.config.triggers;
+        match *event {
+            NotifyEvent::Message {
+                sender,
+                text,
+                mention,
+            } => {
+                let triggered = (mention && triggers.contains(&NotifyTrigger::Mention))
+                    || triggers.contains(&NotifyTrigger::Message)
+                    || self.config.users.iter().any(|user| user == sender);
+                triggered.then(|| Notification {
+                    title: format!("{} in uiop-chat", sanitize(sender)),
+                    body: sanitize(text),
+                })
+            }
+            NotifyEvent::Error(error) => {
+                triggers
+                    .contains(&NotifyTrigger::Error)
+                    .then(|| Notification {
+                        title: String::from("uiop-chat error"),
+                        body: sanitize(error),
+                    })
+            }
+        }
+    }
+
+    /// Sends a notification unless disturbing is off or one was sent too recently
+    pub fn notify(&mut self, event: NotifyEvent) -> Result<()> {
+        let Some(mut notification) = self.notification(&event) else {
+            return Ok(());
+        };
+        if self.config.do_not_disturb {
+            return Ok(());
+        }
+        let interval = Duration::from_secs(self.config.interval);
+        if self.last_sent.is_some_and(|last| last.elapsed() < interval) {
+            self.suppressed += 1;
+            return Ok(());
+        }
+        if self.suppressed > 0 {
+            notification.body = format!("{} (+{} more)", notification.body, self.suppressed);
+        }
+        self.last_sent = Some(Instant::now());
+        self.suppressed = 0;
+
+        let sequences = self.escape_sequences(&notification);
+        if !sequences.is_empty() {
+            let mut stdout = io::stdout();
+            stdout.write_all(sequences.as_bytes())?;
+            stdout.flush()?;
+        }
+        if self.config.outputs.contains(&NotifyOutput::Title) {
+            self.title_marked = true;
+        }
+        if self.config.outputs.contains(&NotifyOutput::Command) {
+            self.run_command(&notification)?;
+        }
+        Ok(())
+    }
+
+    /// Restores the window title once the user is back
+    pub fn clear_title(&mut self) -> Result<()> {
+        if !self.title_marked {
+            return Ok(());
+        }
+        self.title_marked = false;
+        let mut stdout = io::stdout();
+        // Pop the title pushed before marking it
+        stdout.write_all(b"\x1b[23;0t")?;
+        stdout.flush()?;
+        Ok(())
+    }
+
+    fn escape_sequences(&self, notification: &Notification) -> String {
+        let Notification { title, body } = notification;
+        let mut sequences = String::new();
+        for output in &self.config.outputs {
+            match output {
+                NotifyOutput::Bell => sequences.push('\x07'),
+                NotifyOutput::Osc9 => sequences
+                    .push_str(&self.passthrough(&format!("\x1b]9;{}: {}\x07", title, body))),
+                NotifyOutput::Osc777 => sequences.push_str(&self.passthrough(&format!(
+                    "\x1b]777;notify;{};{}\x07",
+                    title.replace(';', ","),
+                    body
+                ))),
+                // Save the current title once, so it can be restored
+                NotifyOutput::Title if !self.title_marked => {
+                    sequences.push_str(&format!("\x1b[22;0t\x1b]2;(*) {}\x07", title))
+                }
+                NotifyOutput::Title => sequences.push_str(&format!("\x1b]2;(*) {}\x07", title)),
+                NotifyOutput::Command => (),
+            }
+        }
+        sequences
+    }
+
+    /// tmux swallows unknown OSC sequences unless they're wrapped for passthrough
+    fn passthrough(&self, sequence: &str) -> String {
+        match self.in_tmux {
+            true => format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b")),
+            false => sequence.to_string(),
+        }
+    }
+
+    fn run_command(&self, notification: &Notification) -> Result<()> {
+        let Some((program, args)) = self.config.command.split_first() else {
+            return Ok(());
+        };
+        let mut child = Command::new(program)
+            .args(args)
+            .arg(&notification.title)
+            .arg(&notification.body)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Failed to run notification command '{}'", program))?;
+        thread::spawn(move || child.wait());
+        Ok(())
+    }
+}
+
+/// Remote text must not be able to inject escape sequences of its own
+fn sanitize(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_control())
+        .take(BODY_LENGTH)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message<'a>(sender: &'a str, text: &'a str, mention: bool) -> NotifyEvent<'a> {
+        NotifyEvent::Message {
+            sender,
+            text,
+            mention,
+        }
+    }
+
+    #[test]
+    fn check_notify_triggers() {
+        let config = NotifyConfig {
+            users: vec![String::from("alice")],
+            ..NotifyConfig::default()
+        };
+        let notifier = Notifier::new(config);
+        assert!(
+            notifier
+                .notification(&message("bob", "hi", false))
+                .is_none()
+        );
+        assert!(
+            notifier
+                .notification(&message("alice", "hi", false))
+                .is_some()
+        );
+        assert!(notifier.notification(&NotifyEvent::Error("oops")).is_none());
+        assert_eq!(
+            notifier.notification(&message("bob", "hi\x1b]2;pwned\x07 johndoe", true)),
+            Some(Notification {
+                title: String::from("bob in uiop-chat"),
+                body: String::from("hi]2;pwned johndoe"),
+            })
+        );
+    }
+
+    #[test]
+    fn check_escape_sequences() {
+        let config = NotifyConfig {
+            outputs: vec![NotifyOutput::Bell, NotifyOutput::Osc777],
+            ..NotifyConfig::default()
+        };
+        let mut notifier = Notifier::new(config);
+        notifier.in_tmux = false;
+        let notification = Notification {
+            title: String::from("a;b"),
+            body: String::from("c;d"),
+        };
+        assert_eq!(
+            notifier.escape_sequences(&notification),
+            "\x07\x1b]777;notify;a,b;c;d\x07"
+        );
+        notifier.in_tmux = true;
+        assert_eq!(
+            notifier.escape_sequences(&notification),
+            "\x07\x1bPtmux;\x1b\x1b]777;notify;a,b;c;d\x07\x1b\\"
+        );
+    }
+}
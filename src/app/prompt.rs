@@ -0,0 +1,173 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Text of the prompt with a cursor, which moves over whole grapheme clusters
+#[derive(Debug, Default, Clone)]
+pub struct PromptLine {
+    text: String,
+    /// Byte offset of the cursor, always on a grapheme cluster boundary
+    cursor: usize,
+}
+
+impl PromptLine {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn before_cursor(&self) -> &str {
+        &self.text[..self.cursor]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// Replaces the text, moving the cursor to its end
+    pub fn set(&mut self, text: String) {
+        self.cursor = text.len();
+        self.text = text;
+    }
+
+    pub fn take(&mut self) -> String {
+        self.cursor = 0;
+        std::mem::take(&mut self.text)
+    }
+
+    pub fn clear(&mut self) {
+        self.take();
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    pub fn insert_str(&mut self, text: &str) {
+        self.text.insert_str(self.cursor, text);
+        self.cursor += text.len();
+    }
+
+    /// Replaces the text between `start` and the cursor, e.g. a word being completed
+    pub fn replace_before_cursor(&mut self, start: usize, text: &str) {
+        self.text.replace_range(start..self.cursor, text);
+        self.cursor = start + text.len();
+    }
+
+    pub fn delete_backward(&mut self) {
+        let start = self.previous_boundary();
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+
+    pub fn delete_forward(&mut self) {
+        let end = self.next_boundary();
+        self.text.replace_range(self.cursor..end, "");
+    }
+
+    pub fn move_left(&mut self) {
+        self.cursor = self.previous_boundary();
+    }
+
+    pub fn move_right(&mut self) {
+        self.cursor = self.next_boundary();
+    }
+
+    pub fn move_home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn move_end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    fn previous_boundary(&self) -> usize {
+        self.before_cursor()
+            .grapheme_indices(true)
+            .next_back()
+            .map_or(0, |(index, _)| index)
+    }
+
+    fn next_boundary(&self) -> usize {
+        let after = &self.text[self.cursor..];
+        self.cursor + after.graphemes(true).next().map_or(0, str::len)
+    }
+
+    /// The part of the text which fits into `width` columns with the cursor in view,
+    /// and the cursor's column within it
+    pub fn view(&self, width: usize) -> (&str, usize) {
+        let mut start = 0;
+        let mut cursor_column = self.before_cursor().width();
+        // Leave a column for the cursor after the last character
+        for (index, grapheme) in self.before_cursor().grapheme_indices(true) {
+            if cursor_column < width {
+                break;
+            }
+            cursor_column -= grapheme.width();
+            start = index + grapheme.len();
+        }
+
+        let mut end = start;
+        let mut used = 0;
+        for grapheme in self.text[start..].graphemes(true) {
+            used += grapheme.width();
+            if used > width {
+                break;
+            }
+            end += grapheme.len();
+        }
+        (&self.text[start..end], cursor_column)
+    }
+}
+
+/// Splits pasted text into lines, dropping control characters and blank lines
+pub fn paste_lines(text: &str) -> Vec<String> {
+    text.replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .split('\n')
+        .map(|line| {
+            line.chars()
+                .map(|c| if c == '\t' { ' ' } else { c })
+                .filter(|c| !c.is_control())
+                .collect::<String>()
+        })
+        .filter(|line| !line.trim().is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_paste_sanitizing() {
+        assert_eq!(paste_lines("hello\tworld\x1b[31m"), vec!["hello world[31m"]);
+        assert_eq!(
+            paste_lines("one\r\ntwo\r\n\nthree\n"),
+            vec!["one", "two", "three"]
+        );
+        assert_eq!(paste_lines("\x07\n \n"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn check_grapheme_editing() {
+        let mut prompt = PromptLine::default();
+        prompt.set(String::from("ae\u{301}👩‍👩‍👧b"));
+        prompt.move_left();
+        prompt.move_left();
+        assert_eq!(prompt.before_cursor(), "ae\u{301}");
+        prompt.delete_backward();
+        assert_eq!(prompt.text(), "a👩‍👩‍👧b");
+        prompt.delete_forward();
+        assert_eq!(prompt.text(), "ab");
+        prompt.insert('中');
+        prompt.move_right();
+        prompt.move_right();
+        assert_eq!(prompt.text(), "a中b");
+        assert_eq!(prompt.view(10), ("a中b", 4));
+
+        prompt.set(String::from("中文字符"));
+        assert_eq!(prompt.view(5), ("字符", 4));
+        prompt.move_home();
+        assert_eq!(prompt.view(5), ("中文", 0));
+    }
+}
@@ -0,0 +1,53 @@
+use std::{path::Path, sync::mpsc::Sender, thread, time::Duration};
+
+use anyhow::{Context, Result, ensure};
+use chrono::{DateTime, FixedOffset};
+
+use super::event::AppEvent;
+use crate::{
+    capture::{Direction, read_capture},
+    codec::parse_payload,
+    protocol::DspPayload,
+};
+
+/// Payloads a recorded session received, played back instead of a connection
+pub struct Replay {
+    payloads: Vec<(DateTime<FixedOffset>, DspPayload)>,
+    speed: f64,
+}
+
+impl Replay {
+    /// Sent frames are left out, the server's echoes of them were recorded too.
+    /// Received frames which don't parse are skipped.
+    pub fn read(path: &Path, speed: f64) -> Result<Replay> {
+        ensure!(speed > 0.0, "Replay speed has to be above 0");
+        let mut payloads = vec![];
+        for record in read_capture(path)? {
+            if record.direction != Direction::ToClient {
+                continue;
+            }
+            let time = DateTime::parse_from_rfc3339(&record.time)
+                .with_context(|| format!("Invalid time '{}' in capture file", record.time))?;
+            if let Ok(payload) = parse_payload(record.frame) {
+                payloads.push((time, payload));
+            }
+        }
+        Ok(Replay { payloads, speed })
+    }
+
+    /// Sends the payloads with the recorded gaps between them, divided by the speed
+    pub fn run(self, tx: Sender<AppEvent>) {
+        let mut previous = None;
+        for (time, payload) in self.payloads {
+            if let Some(previous) = previous {
+                let gap = time.signed_duration_since(previous).to_std();
+                thread::sleep(gap.unwrap_or(Duration::ZERO).div_f64(self.speed));
+            }
+            previous = Some(time);
+            if tx.send(AppEvent::PayloadReceived(payload)).is_err() {
+                return;
+            }
+        }
+        let _ = tx.send(AppEvent::ReplayFinished());
+    }
+}
@@ -0,0 +1,33 @@
+use std::collections::BTreeSet;
+
+/// Users which are known to be on the server, as seen from JOIN, MESSAGE and QUIT payloads
+#[derive(Default)]
+pub struct Roster {
+    users: BTreeSet<String>,
+}
+
+impl Roster {
+    pub fn seen(&mut self, username: &str) {
+        if !self.users.contains(username) {
+            self.users.insert(username.to_string());
+        }
+    }
+
+    pub fn left(&mut self, username: &str) {
+        self.users.remove(username);
+    }
+
+    pub fn users(&self) -> impl Iterator<Item = &String> {
+        self.users.iter()
+    }
+
+    /// Usernames starting with `prefix`, ignoring case
+    pub fn complete(&self, prefix: &str) -> Vec<String> {
+        let prefix = prefix.to_lowercase();
+        self.users
+            .iter()
+            .filter(|user| user.to_lowercase().starts_with(&prefix))
+            .cloned()
+            .collect()
+    }
+}
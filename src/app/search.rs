@@ -0,0 +1,195 @@
+use std::{ops::Range, str::FromStr};
+
+use log::Level;
+use regex::{Regex, RegexBuilder};
+use strum_macros::EnumString;
+
+use super::chat::{ChatKind, ChatLine};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum KindFilter {
+    Message,
+    Join,
+    Quit,
+    Notice,
+    Error,
+    Warn,
+}
+
+impl KindFilter {
+    fn matches(&self, line: &ChatLine) -> bool {
+        match self {
+            KindFilter::Message => line.kind == ChatKind::Message,
+            KindFilter::Join => line.kind == ChatKind::Join,
+            KindFilter::Quit => line.kind == ChatKind::Quit,
+            KindFilter::Notice => line.kind == ChatKind::Notice,
+            KindFilter::Error => line.level == Level::Error,
+            KindFilter::Warn => line.level == Level::Warn,
+        }
+    }
+}
+
+/// What to look for in the chat history
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    pattern: Option<Regex>,
+    sender: Option<String>,
+    kind: Option<KindFilter>,
+}
+
+impl SearchQuery {
+    /// Parses e.g. `from:alice kind:message hello`, text is matched literally
+    /// unless it's wrapped in slashes like `/hel+o/`
+    pub fn parse(input: &str) -> Result<SearchQuery, String> {
+        let mut sender = None;
+        let mut kind = None;
+        let mut words = vec![];
+        for word in input.split_whitespace() {
+            if let Some(username) = word.strip_prefix("from:") {
+                sender = Some(username.to_string());
+            } else if let Some(name) = word.strip_prefix("kind:") {
+                let filter = KindFilter::from_str(name).map_err(|_| {
+                    format!(
+                        "Unknown kind '{}', expected message, join, quit, notice, error or warn",
+                        name
+                    )
+                })?;
+                kind = Some(filter);
+            } else {
+                words.push(word);
+            }
+        }
+
+        let text = words.join(" ");
+        let pattern = match text.strip_prefix('/').and_then(|t| t.strip_suffix('/')) {
+            Some(regex) if !regex.is_empty() => Some(regex.to_string()),
+            _ if text.is_empty() => None,
+            _ => Some(regex::escape(&text)),
+        };
+        let pattern = pattern
+            .map(|pattern| {
+                RegexBuilder::new(&pattern)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|err| format!("Invalid regex: {}", err))
+            })
+            .transpose()?;
+
+        if pattern.is_none() && sender.is_none() && kind.is_none() {
+            return Err(String::from("Nothing to search for"));
+        }
+        Ok(SearchQuery {
+            pattern,
+            sender,
+            kind,
+        })
+    }
+
+    pub fn matches(&self, line: &ChatLine) -> bool {
+        let sender_matches = match (&self.sender, &line.sender) {
+            (None, _) => true,
+            (Some(wanted), Some(sender)) => wanted.eq_ignore_ascii_case(sender),
+            (Some(_), None) => false,
+        };
+        sender_matches
+            && self.kind.is_none_or(|kind| kind.matches(line))
+            && self
+                .pattern
+                .as_ref()
+                .is_none_or(|pattern| pattern.is_match(&line.text))
+    }
+
+    /// Byte ranges of a line's text to highlight
+    pub fn find(&self, line: &ChatLine) -> Vec<Range<usize>> {
+        match &self.pattern {
+            Some(pattern) if self.matches(line) => pattern
+                .find_iter(&line.text)
+                .map(|found| found.range())
+                .filter(|range| !range.is_empty())
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+/// State of the search bar, which replaces the prompt while open
+#[derive(Debug, Default)]
+pub struct Search {
+    pub input: String,
+    /// Whether keys are typed into the search bar, otherwise they jump between matches
+    pub editing: bool,
+    pub query: Option<SearchQuery>,
+    pub error: Option<String>,
+    /// Id of the chat line of the selected match
+    pub current: Option<usize>,
+    /// Which match is selected, counting from the newest one
+    pub position: usize,
+    pub match_count: usize,
+}
+
+impl Search {
+    pub fn new() -> Search {
+        Search {
+            editing: true,
+            ..Search::default()
+        }
+    }
+
+    /// Counts a line added while searching, the selected match moves one further from the newest
+    pub fn line_added(&mut self, line: &ChatLine) {
+        if self.query.as_ref().is_some_and(|query| query.matches(line)) {
+            self.match_count += 1;
+            if self.current.is_some() {
+                self.position += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(sender: &str, text: &str) -> ChatLine {
+        ChatLine::message(sender.to_string(), text.to_string(), vec![])
+    }
+
+    #[test]
+    fn check_search_query() {
+        let query = SearchQuery::parse("from:alice Hello").unwrap();
+        assert!(query.matches(&message("Alice", "oh, hello there")));
+        assert!(!query.matches(&message("bob", "hello")));
+        assert_eq!(
+            query.find(&message("alice", "hello HELLO")),
+            vec![0..5, 6..11]
+        );
+
+        let query = SearchQuery::parse("a.c").unwrap();
+        assert!(!query.matches(&message("bob", "abc")));
+        let query = SearchQuery::parse("/a.c/").unwrap();
+        assert!(query.matches(&message("bob", "abc")));
+
+        let query = SearchQuery::parse("kind:error").unwrap();
+        assert!(query.matches(&ChatLine::notice(Level::Error, String::from("oops"))));
+        assert!(!query.matches(&message("bob", "oops")));
+
+        assert!(SearchQuery::parse("kind:nope").is_err());
+        assert!(SearchQuery::parse("/(/").is_err());
+        assert!(SearchQuery::parse("  ").is_err());
+    }
+
+    #[test]
+    fn check_lines_added_while_searching() {
+        let mut search = Search {
+            query: SearchQuery::parse("hello").ok(),
+            current: Some(0),
+            position: 1,
+            match_count: 1,
+            ..Search::new()
+        };
+        search.line_added(&message("bob", "bye"));
+        search.line_added(&message("bob", "hello again"));
+        assert_eq!((search.position, search.match_count), (2, 2));
+    }
+}
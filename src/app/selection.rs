@@ -0,0 +1,75 @@
+use std::ops::RangeInclusive;
+
+use super::chat::ChatLine;
+
+/// State of the message selection mode, which replaces key handling while open
+#[derive(Debug)]
+pub struct Selection {
+    /// Id of the chat line under the cursor
+    pub cursor: usize,
+    /// Id of the line where a range selection was started
+    pub anchor: Option<usize>,
+}
+
+impl Selection {
+    pub fn new(cursor: usize) -> Selection {
+        Selection {
+            cursor,
+            anchor: None,
+        }
+    }
+
+    /// Ids of the selected lines, only the cursor's line unless a range was started
+    pub fn range(&self) -> RangeInclusive<usize> {
+        let anchor = self.anchor.unwrap_or(self.cursor);
+        anchor.min(self.cursor)..=anchor.max(self.cursor)
+    }
+
+    pub fn toggle_range(&mut self) {
+        self.anchor = match self.anchor {
+            Some(_) => None,
+            None => Some(self.cursor),
+        };
+    }
+}
+
+/// Chat lines as copied to the clipboard, one per line.
+/// Only the message texts are kept without `with_metadata`,
+/// otherwise lines look like they do in the chat.
+pub fn yank_text<'a>(lines: impl Iterator<Item = &'a ChatLine>, with_metadata: bool) -> String {
+    lines
+        .map(|line| match with_metadata {
+            true => format!("{} {}", line.time.format("%H:%M:%S"), line),
+            false => line.text.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_yank_text() {
+        let mut selection = Selection::new(5);
+        selection.toggle_range();
+        selection.cursor = 3;
+        assert_eq!(selection.range(), 3..=5);
+
+        let mut repeated = ChatLine::message(String::from("bob"), String::from("hi"), vec![]);
+        repeated.repeats = 2;
+        let lines = [
+            repeated,
+            ChatLine::join(String::from("alice")),
+            ChatLine::message(String::from("alice"), String::from("hello"), vec![]),
+        ];
+        assert_eq!(
+            yank_text(lines.iter(), false),
+            "hi\nUser 'alice' has joined the server\nhello"
+        );
+        let full = yank_text(lines.iter(), true);
+        assert_eq!(full.lines().count(), 3);
+        assert!(full.lines().next().unwrap().ends_with(" [bob] hi ×2"));
+    }
+}
@@ -1,4 +1,6 @@
 use std::{
+    collections::VecDeque,
+    fs,
     sync::{
         Arc,
         mpsc::{self, Receiver, Sender},
@@ -7,61 +9,186 @@ use std::{
     time::Duration,
 };
 
-use super::{event::AppEvent, mode::AppMode};
+use super::{
+    chat::{ChatLine, ChatLog, MentionMatcher},
+    clipboard::copy_to_clipboard,
+    command::{COMMANDS, Command, PromptInput, complete_command, parse_prompt},
+    completion::{Completion, last_word},
+    emoji::{complete_shortcode, expand_shortcodes, trailing_shortcode},
+    event::AppEvent,
+    keymap::Action,
+    layout::Pane,
+    links::{LinkPicker, open_link},
+    mode::AppMode,
+    notify::{Notifier, NotifyEvent},
+    prompt::{PromptLine, paste_lines},
+    replay::Replay,
+    roster::Roster,
+    search::{Search, SearchQuery},
+    selection::{Selection, yank_text},
+    status::{ConnectionState, SessionStatus},
+};
 use crate::app::crossterm_backend::*;
 use crate::{
-    client::{DspReader, DspWriter},
-    config::DspClientConfig,
+    client::{DspClient, DspReader, DspWriter},
+    codec::parse_payload,
+    config::{DspClientConfig, DspUiConfig, FileConfig},
     logger::{NS_APP, NS_CHAT},
     protocol::{DspMessage, DspPayload, MessageMessage, QuitMessage},
 };
 use anyhow::{Context, anyhow};
+use chrono::{DateTime, Local};
 use log::*;
-use tokio::sync::Mutex;
-use tui_logger::*;
+use ratatui::layout::{Position, Rect};
+use tokio::{sync::Mutex, task::AbortHandle};
+
+/// Where received payloads come from, taken once the app starts
+enum PayloadSource {
+    Connection(DspReader),
+    Replay(Replay),
+}
 
 pub struct AppState {
-    client_reader: Option<DspReader>,
+    payload_source: Option<PayloadSource>,
+    /// Whether a recorded session is played back, nothing is sent then
+    pub replaying: bool,
     client_writer: Arc<Mutex<Option<DspWriter>>>,
+    /// Counts reconnects, writers and clients of older connections are dropped
+    connection: u64,
+    receive_task: Option<AbortHandle>,
+    outbox: VecDeque<DspPayload>,
     pub client_config: DspClientConfig,
+    pub ui_config: DspUiConfig,
     app_event_rx: Option<Receiver<AppEvent>>,
     app_event_tx: Sender<AppEvent>,
     pub mode: AppMode,
     pub tab_names: Vec<&'static str>,
-    pub log_state: TuiWidgetState,
+    pub chat: ChatLog,
+    pub roster: Roster,
+    mention_matcher: MentionMatcher,
+    pub unread_mentions: usize,
     pub selected_tab: usize,
-    pub active_message: String,
+    pub active_message: PromptLine,
+    pub prompt_error: Option<String>,
+    /// Lines of a multi-line paste waiting for confirmation before being sent
+    pub pending_paste: Option<Vec<String>>,
+    pub search: Option<Search>,
+    pub link_picker: Option<LinkPicker>,
+    pub selection: Option<Selection>,
+    /// Whether keys toggle and resize panes
+    pub layout_mode: bool,
+    pub status: SessionStatus,
+    notifier: Notifier,
+    pub areas: UiAreas,
+    /// Size of the terminal, which decides what the layout shows
+    pub screen: Rect,
+    completion: Option<Completion>,
 }
 
+/// Screen areas from the last render, used to find what was clicked
+#[derive(Default)]
+pub struct UiAreas {
+    pub tabs: Vec<Rect>,
+    pub prompt: Rect,
+    pub new_messages: Rect,
+    /// Where the terminal cursor is shown, hidden when `None`
+    pub cursor: Option<Position>,
+}
+
+/// Amount of rows scrolled by a single mouse wheel step
+const MOUSE_SCROLL_ROWS: usize = 3;
+
 impl AppState {
     pub fn new(
         client_reader: DspReader,
         client_writer: DspWriter,
         client_config: DspClientConfig,
+        ui_config: DspUiConfig,
+    ) -> AppState {
+        let source = PayloadSource::Connection(client_reader);
+        let mut state =
+            AppState::with_source(source, Some(client_writer), client_config, ui_config);
+        state.load_history();
+        state
+    }
+
+    /// Plays a recorded session back without connecting
+    pub fn replay(
+        replay: Replay,
+        client_config: DspClientConfig,
+        ui_config: DspUiConfig,
+    ) -> AppState {
+        let mut state = AppState::with_source(
+            PayloadSource::Replay(replay),
+            None,
+            client_config,
+            ui_config,
+        );
+        state.status.connection = ConnectionState::Disconnected;
+        state
+    }
+
+    fn with_source(
+        payload_source: PayloadSource,
+        client_writer: Option<DspWriter>,
+        client_config: DspClientConfig,
+        ui_config: DspUiConfig,
     ) -> AppState {
         let (app_event_tx, app_event_rx) = mpsc::channel::<AppEvent>();
 
-        let log_state = TuiWidgetState::new().set_default_display_level(LevelFilter::Trace);
+        let mention_matcher =
+            MentionMatcher::new(&client_config.username, &ui_config.highlight_words);
+        let notifier = Notifier::new(ui_config.notify.clone());
 
         // Adding this line had provoked the bug as described in issue #69
         let tab_names = vec!["Message", "Quit"];
         AppState {
-            client_reader: Some(client_reader),
-            client_writer: Arc::new(Mutex::new(Some(client_writer))),
+            replaying: matches!(payload_source, PayloadSource::Replay(_)),
+            payload_source: Some(payload_source),
+            client_writer: Arc::new(Mutex::new(client_writer)),
+            connection: 0,
+            receive_task: None,
+            outbox: VecDeque::new(),
             client_config,
+            ui_config,
             app_event_tx,
             app_event_rx: Some(app_event_rx),
             mode: AppMode::Run,
-            log_state,
+            chat: ChatLog::default(),
+            roster: Roster::default(),
+            mention_matcher,
+            unread_mentions: 0,
             tab_names,
             selected_tab: 0,
-            active_message: String::from(""),
+            active_message: PromptLine::default(),
+            prompt_error: None,
+            pending_paste: None,
+            search: None,
+            link_picker: None,
+            selection: None,
+            layout_mode: false,
+            status: SessionStatus::new(),
+            notifier,
+            areas: UiAreas::default(),
+            screen: Rect::default(),
+            completion: None,
         }
     }
 
     fn send_ui_fatal(tx: Sender<AppEvent>, error: String) {
         loop {
-            if let None = tx.send(AppEvent::FatalError(error.clone())).err() {
+            if tx.send(AppEvent::FatalError(error.clone())).is_ok() {
+                return;
+            }
+        }
+    }
+
+    fn send_ui_disconnected(tx: Sender<AppEvent>, connection: u64, error: String) {
+        loop {
+            if tx
+                .send(AppEvent::Disconnected((connection, error.clone())))
+                .is_ok()
+            {
                 return;
             }
         }
@@ -69,31 +196,51 @@ impl AppState {
 
     pub fn start(&mut self) -> anyhow::Result<Receiver<AppEvent>> {
         // Use an mpsc::channel to combine stdin events with app events
+        let event_rx = self.start_headless()?;
+        let event_tx = self.app_event_tx.clone();
+        let clock_tx = self.app_event_tx.clone();
+        thread::spawn(move || input_thread(event_tx));
+        thread::spawn(move || clock_thread(clock_tx));
+
+        Ok(event_rx)
+    }
+
+    /// Starts receiving payloads without reading the terminal, so tests can script the events
+    pub fn start_headless(&mut self) -> anyhow::Result<Receiver<AppEvent>> {
         let event_rx = self
             .app_event_rx
             .take()
             .ok_or(anyhow!("App initialized without UI event receiver"))?;
-        let event_tx = self.app_event_tx.clone();
-        let error_tx = self.app_event_tx.clone();
-        let payload_receive_tx = self.app_event_tx.clone();
-        let client_reader = self
-            .client_reader
+        let payload_source = self
+            .payload_source
             .take()
             .ok_or(anyhow!("App initialized without DSP reader"))?;
+        match payload_source {
+            PayloadSource::Connection(client_reader) => self.spawn_payload_receiver(client_reader),
+            PayloadSource::Replay(replay) => {
+                let replay_tx = self.app_event_tx.clone();
+                thread::spawn(move || replay.run(replay_tx));
+            }
+        }
 
-        thread::spawn(move || input_thread(event_tx));
-        tokio::spawn(async move {
+        Ok(event_rx)
+    }
+
+    fn spawn_payload_receiver(&mut self, client_reader: DspReader) {
+        let error_tx = self.app_event_tx.clone();
+        let payload_receive_tx = self.app_event_tx.clone();
+        let connection = self.connection;
+        let task = tokio::spawn(async move {
             let task = payload_receive_task(client_reader, payload_receive_tx);
             if let Some(err) = task
                 .await
-                .with_context(|| format!("Connection closed, you need to restart the client"))
+                .context("Connection closed, use /reconnect to connect again")
                 .err()
             {
-                AppState::send_ui_fatal(error_tx, err.to_string());
+                AppState::send_ui_disconnected(error_tx, connection, err.to_string());
             };
         });
-
-        Ok(event_rx)
+        self.receive_task = Some(task.abort_handle());
     }
 
     pub fn rerender(&mut self) {
@@ -101,9 +248,69 @@ impl AppState {
         let _ = self.app_event_tx.send(AppEvent::Rerender());
     }
 
-    pub fn rerender_chat(&mut self) {
-        self.log_state.transition(TuiWidgetEvent::EscapeKey);
-        self.rerender();
+    /// Adds a line to the chat view and mirrors it into the log
+    pub fn push_chat(&mut self, line: ChatLine) {
+        log!(target: NS_CHAT, line.level, "{}", line);
+        self.push_line(line);
+    }
+
+    /// Adds a line to the chat view only, keeping the search's match count up to date
+    fn push_line(&mut self, line: ChatLine) {
+        if let Some(search) = &mut self.search {
+            search.line_added(&line);
+        }
+        self.chat.push(line);
+    }
+
+    pub fn push_notice(&mut self, level: Level, text: &str) {
+        self.push_chat(ChatLine::notice(level, text.to_string()));
+    }
+
+    /// Shows the newest messages of earlier sessions above the live ones,
+    /// filtered and highlighted like received ones but without notifying
+    fn load_history(&mut self) {
+        let Some(history) = &self.client_config.history else {
+            return;
+        };
+        let records = match history.recent_messages() {
+            Ok(records) => records,
+            Err(err) => {
+                warn!(target: NS_APP, "Failed to load chat history: {:#}", err);
+                return;
+            }
+        };
+        if records.is_empty() {
+            return;
+        }
+        let loaded = format!("Messages from earlier sessions: {}", records.len());
+        self.push_notice(Level::Info, &loaded);
+        for record in records {
+            let Some(DspPayload {
+                username,
+                message: DspMessage::MessageMessage(m),
+            }) = record.payload
+            else {
+                continue;
+            };
+            let own = username == self.client_config.username;
+            if !own && self.ui_config.filter.hides_message(&username, &m.text) {
+                continue;
+            }
+            let text = match self.ui_config.emoji.render_incoming {
+                true => expand_shortcodes(&m.text),
+                false => m.text,
+            };
+            let mentions = match own {
+                true => vec![],
+                false => self.mention_matcher.find(&text),
+            };
+            let mut line = ChatLine::message(username, text, mentions);
+            if let Ok(time) = DateTime::parse_from_rfc3339(&record.time) {
+                line.time = time.with_timezone(&Local);
+            }
+            self.chat.push(line);
+        }
+        self.chat.end_history();
     }
 
     pub fn react_to_payload(&mut self, payload: DspPayload) {
@@ -111,118 +318,666 @@ impl AppState {
         let message = payload.message;
         match message {
             DspMessage::JoinMessage(_) => {
-                debug!(target: NS_CHAT, "User '{}' has joined the server", username)
+                self.roster.seen(&username);
+                match self.ui_config.filter.hides_joins() {
+                    true => self.status.hidden += 1,
+                    false => self.push_chat(ChatLine::join(username)),
+                }
             }
             DspMessage::QuitMessage(_) => {
-                debug!(target: NS_CHAT, "User '{}' has left the server", username)
+                self.roster.left(&username);
+                match self.ui_config.filter.hides_joins() {
+                    true => self.status.hidden += 1,
+                    false => self.push_chat(ChatLine::quit(username)),
+                }
+            }
+            DspMessage::MessageMessage(m) => {
+                self.roster.seen(&username);
+                if username == self.client_config.username {
+                    self.status.echo_received(&m.text);
+                } else {
+                    self.status.received += 1;
+                }
+                let own = username == self.client_config.username;
+                let filter = &self.ui_config.filter;
+                if !own && filter.hides_message(&username, &m.text) {
+                    self.status.hidden += 1;
+                    self.rerender();
+                    return;
+                }
+                let text = match self.ui_config.emoji.render_incoming {
+                    true => expand_shortcodes(&m.text),
+                    false => m.text,
+                };
+                if filter.collapses_repeats() && self.chat.repeat_last(&username, &text) {
+                    self.status.hidden += 1;
+                    self.rerender();
+                    return;
+                }
+                let mentions = match own {
+                    true => vec![],
+                    false => self.mention_matcher.find(&text),
+                };
+                self.unread_mentions += mentions.len();
+                if !own {
+                    self.notify(NotifyEvent::Message {
+                        sender: &username,
+                        text: &text,
+                        mention: !mentions.is_empty(),
+                    });
+                }
+                self.push_chat(ChatLine::message(username, text, mentions));
             }
-            DspMessage::MessageMessage(m) => info!(target: NS_CHAT, "[{}] {}", username, m.text),
             DspMessage::ChallengeMessage(_) => {
-                error!(target: NS_CHAT, "You've received a rate-limiting challenge which is not implemented. It's left unimplemented, you might get disconnected.")
+                self.status.challenge_pending = true;
+                self.push_notice(
+                    Level::Error,
+                    "The server is rate limiting you with a challenge, which this client can't answer. Your messages are dropped until the server rescinds it, and it may disconnect you once the challenge times out.",
+                )
             }
             DspMessage::RescindedMessage(_) => {
-                warn!(target: NS_CHAT, "The challenge has been rescinded, you can chat again")
+                self.status.challenge_pending = false;
+                self.push_notice(
+                    Level::Warn,
+                    "The challenge has been rescinded, you can chat again",
+                )
             }
-            DspMessage::ResponseMessage(_) => {
-                warn!(target: NS_CHAT, "You've received a challenge response, this shouldn't happen. Inform server admin.")
+            DspMessage::ResponseMessage(_) => self.push_notice(
+                Level::Warn,
+                "You've received a challenge response, this shouldn't happen. Inform server admin.",
+            ),
+            DspMessage::ErrorMessage(m) => {
+                self.push_notice(Level::Error, &format!("Server error: {}", m.text));
+                self.notify(NotifyEvent::Error(&m.text));
+                self.status.last_error = Some(m.text);
             }
-            DspMessage::ErrorMessage(m) => error!(target: NS_CHAT, "Server error: {}", m.text),
         }
-        self.rerender_chat();
+        self.rerender();
     }
 
     fn handle_ui_event(&mut self, event: Event) {
-        let selected_tab = self.selected_tab;
+        if let Event::Key(_) = event {
+            self.clear_title();
+        }
+        match event {
+            Event::Key(key) => self.handle_key_event(key),
+            Event::Mouse(mouse) => self.handle_mouse_event(mouse),
+            Event::Paste(text) => self.paste(&text),
+            Event::Resize(width, height) => self.resize(width, height),
+            _ => (),
+        }
+    }
 
-        if let Event::Key(key) = event {
-            let code = key.code;
+    pub fn resize(&mut self, width: u16, height: u16) {
+        self.screen = Rect::new(0, 0, width, height);
+        self.layout_changed();
+    }
 
-            match code.into() {
-                // Tab switching
-                Key::Char('\t') => self.next_tab(),
-                Key::Tab => self.next_tab(),
+    fn shows_tabs(&self) -> bool {
+        self.ui_config.layout.areas(self.screen).tabs.is_some()
+    }
 
-                // Chat scrolling
-                Key::Esc => self.log_state.transition(TuiWidgetEvent::EscapeKey),
-                Key::PageUp => self.log_state.transition(TuiWidgetEvent::PrevPageKey),
-                Key::PageDown => self.log_state.transition(TuiWidgetEvent::NextPageKey),
+    /// Hiding the tabs goes back to the Message tab, a hidden Quit tab would swallow typing
+    fn layout_changed(&mut self) {
+        if !self.shows_tabs() {
+            self.selected_tab = 0;
+        }
+    }
 
-                // Message sending
-                Key::Char(c) if selected_tab == 0 => self.add_active_message(c),
-                Key::Backspace if selected_tab == 0 => self.backspace_active_message(),
-                Key::Enter if selected_tab == 0 => self.send_active_message(),
+    fn handle_key_event(&mut self, key: KeyEvent) {
+        if key.kind == KeyEventKind::Release {
+            return;
+        }
+        let action = self.ui_config.keymap.action(key);
+        if self.pending_paste.is_some() && action != Some(Action::Quit) {
+            self.confirm_paste(key);
+            return;
+        }
+        if self.search.is_some()
+            && action != Some(Action::Quit)
+            && self.handle_search_key(key, action)
+        {
+            return;
+        }
+        if self.link_picker.is_some() && action != Some(Action::Quit) {
+            self.handle_link_picker_key(key);
+            return;
+        }
+        if self.selection.is_some() && action != Some(Action::Quit) {
+            self.handle_selection_key(key, action);
+            return;
+        }
+        if self.layout_mode && action != Some(Action::Quit) {
+            self.handle_layout_key(key, action);
+            return;
+        }
 
-                // Quitting
-                Key::Enter if selected_tab == 1 => self.trigger_quit(),
+        // Any other key press ends the completion cycle
+        if action != Some(Action::Complete) {
+            self.completion = None;
+        }
+        self.prompt_error = None;
 
+        match (action, key.code) {
+            (Some(action), _) => self.handle_action(action),
+            // A slash starts a command, unless we're reading back through the chat
+            (None, Key::Char('/')) if self.active_message.is_empty() && self.chat.is_scrolled() => {
+                self.open_search()
+            }
+            (None, Key::Char(c)) if self.selected_tab == 0 && is_text_input(key) => {
+                self.add_active_message(c)
+            }
+            (None, code) if self.selected_tab == 0 => match code {
+                Key::Left => self.active_message.move_left(),
+                Key::Right => self.active_message.move_right(),
+                Key::Home => self.active_message.move_home(),
+                Key::End => self.active_message.move_end(),
+                Key::Delete => self.active_message.delete_forward(),
                 _ => (),
+            },
+            _ => (),
+        }
+    }
+
+    fn handle_mouse_event(&mut self, mouse: MouseEvent) {
+        let position = Position::new(mouse.column, mouse.row);
+        match mouse.kind {
+            MouseEventKind::ScrollUp => self.chat.scroll_up_by(MOUSE_SCROLL_ROWS),
+            MouseEventKind::ScrollDown => self.chat.scroll_down_by(MOUSE_SCROLL_ROWS),
+            MouseEventKind::Down(MouseButton::Left) => {
+                let clicked_tab = self
+                    .areas
+                    .tabs
+                    .iter()
+                    .position(|tab| tab.contains(position));
+                if let Some(tab) = clicked_tab {
+                    self.selected_tab = tab;
+                } else if self.areas.new_messages.contains(position) {
+                    self.read_to_bottom();
+                } else if self.areas.prompt.contains(position) {
+                    self.selected_tab = 0;
+                } else if let Some(url) = self.chat.link_at(position) {
+                    let url = url.to_string();
+                    self.open_link(&url);
+                } else if let Some(sender) = self.chat.sender_at(position) {
+                    let sender = sender.to_string();
+                    self.insert_username(&sender);
+                }
             }
+            _ => (),
+        }
+    }
+
+    pub fn handle_action(&mut self, action: Action) {
+        let selected_tab = self.selected_tab;
+
+        match action {
+            // Username and command completion, otherwise tab switching
+            Action::Complete if selected_tab == 0 && self.can_complete() => self.complete(),
+            Action::Complete | Action::NextTab => self.next_tab(),
+
+            // Chat scrolling
+            Action::ScrollToBottom => self.read_to_bottom(),
+            Action::ScrollUp => self.chat.scroll_up(),
+            Action::ScrollDown => self.chat.scroll_down(),
+
+            // Message sending
+            Action::DeleteBackward if selected_tab == 0 => self.backspace_active_message(),
+            Action::ClearPrompt if selected_tab == 0 => self.active_message.clear(),
+            Action::SendMessage if selected_tab == 0 => self.submit_active_message(),
+
+            Action::Search => self.open_search(),
+            Action::Links => self.open_link_picker(),
+            Action::Select => self.open_selection(),
+            Action::Layout => self.layout_mode = true,
+
+            // Quitting
+            Action::SendMessage if selected_tab == 1 => self.trigger_quit(),
+            Action::Quit => self.trigger_quit(),
+
+            _ => (),
         }
     }
+
     pub fn handle_app_event(&mut self, event: AppEvent) {
         match event {
             AppEvent::UiEvent(event) => self.handle_ui_event(event),
             AppEvent::PayloadReceived(payload) => self.react_to_payload(payload),
-            AppEvent::PayloadSent((writer, payload)) => match payload.message {
-                DspMessage::QuitMessage(_) => self.mode = AppMode::Quit,
-                DspMessage::MessageMessage(_) => self.active_message_sent(writer),
-                _ => {}
+            AppEvent::PayloadSent((connection, writer, payload)) => match payload.message {
+                DspMessage::QuitMessage(_) => {
+                    self.clear_title();
+                    self.mode = AppMode::Quit;
+                }
+                _ => self.payload_sent(connection, writer),
             },
+            AppEvent::SendFailed((connection, payload, error)) => {
+                self.send_failed(connection, payload, error)
+            }
+            AppEvent::Reconnected((connection, client)) => self.reconnected(connection, client),
+            // Failures of connections replaced by a reconnect don't concern the current one
+            AppEvent::Disconnected((connection, error)) if connection == self.connection => {
+                self.disconnected(error)
+            }
+            AppEvent::Disconnected((_, error)) => {
+                debug!(target: NS_APP, "Ignoring a replaced connection's error: {}", error)
+            }
+            AppEvent::ReplayFinished() => {
+                self.push_notice(Level::Info, "Replay finished");
+                self.rerender();
+            }
             AppEvent::FatalError(error) => {
                 error!(target: NS_APP, "{}", error);
-                self.rerender_chat();
+                self.status.last_error = Some(error.clone());
+                self.push_line(ChatLine::notice(Level::Error, error));
+                self.rerender();
             }
             AppEvent::Rerender() => {}
         }
     }
 
+    /// Returns whether the key was used by the search bar
+    fn handle_search_key(&mut self, key: KeyEvent, action: Option<Action>) -> bool {
+        let Some(search) = &mut self.search else {
+            return false;
+        };
+        if search.editing {
+            match (action, key.code) {
+                (Some(Action::ScrollUp | Action::ScrollDown), _) => return false,
+                (_, Key::Enter) => self.commit_search(),
+                (_, Key::Esc) => self.search = None,
+                (Some(Action::DeleteBackward), _) => {
+                    search.input.pop();
+                }
+                (Some(Action::ClearPrompt), _) => search.input.clear(),
+                (None, Key::Char(c)) if is_text_input(key) => search.input.push(c),
+                _ => (),
+            }
+            return true;
+        }
+        match (action, key.code) {
+            (_, Key::Char('n')) => self.next_match(true),
+            (_, Key::Char('N')) => self.next_match(false),
+            (_, Key::Esc | Key::Enter) => self.search = None,
+            (Some(Action::Search), _) | (_, Key::Char('/')) => search.editing = true,
+            (Some(_), _) => return false,
+            _ => (),
+        }
+        true
+    }
+
+    pub fn open_search(&mut self) {
+        match &mut self.search {
+            Some(search) => search.editing = true,
+            None => self.search = Some(Search::new()),
+        }
+    }
+
+    /// Starts searching from the newest line
+    fn commit_search(&mut self) {
+        let Some(search) = &mut self.search else {
+            return;
+        };
+        let query = match SearchQuery::parse(&search.input) {
+            Ok(query) => query,
+            Err(error) => {
+                search.error = Some(error);
+                return;
+            }
+        };
+        search.match_count = self.chat.lines().filter(|line| query.matches(line)).count();
+        search.current = self.chat.find_older(None, |line| query.matches(line));
+        search.position = 1;
+        search.error = search.current.is_none().then(|| String::from("No matches"));
+        search.editing = false;
+        if let Some(id) = search.current {
+            self.chat.jump_to(id);
+        }
+        search.query = Some(query);
+    }
+
+    /// Jumps to the next older or newer match
+    fn next_match(&mut self, older: bool) {
+        let Some(search) = &mut self.search else {
+            return;
+        };
+        let (Some(query), Some(current)) = (&search.query, search.current) else {
+            return;
+        };
+        let found = match older {
+            true => self
+                .chat
+                .find_older(Some(current), |line| query.matches(line)),
+            false => self.chat.find_newer(current, |line| query.matches(line)),
+        };
+        match found {
+            Some(id) => {
+                search.current = Some(id);
+                search.position = match older {
+                    true => search.position + 1,
+                    false => search.position.saturating_sub(1).max(1),
+                };
+                search.error = None;
+                self.chat.jump_to(id);
+            }
+            None if older => search.error = Some(String::from("No older matches")),
+            None => search.error = Some(String::from("No newer matches")),
+        }
+    }
+
+    pub fn open_link_picker(&mut self) {
+        let links = self.chat.visible_links();
+        match links.is_empty() {
+            true => self.prompt_error = Some(String::from("No links in view")),
+            false => self.link_picker = Some(LinkPicker::new(links)),
+        }
+    }
+
+    fn handle_link_picker_key(&mut self, key: KeyEvent) {
+        let Some(picker) = &mut self.link_picker else {
+            return;
+        };
+        match key.code {
+            Key::Up | Key::Char('k') => picker.select_previous(),
+            Key::Down | Key::Char('j') => picker.select_next(),
+            Key::Char(c @ '1'..='9') => {
+                let index = c as usize - '1' as usize;
+                if index < picker.links.len() {
+                    picker.selected = index;
+                    self.choose_link(false);
+                }
+            }
+            Key::Enter => self.choose_link(false),
+            Key::Char('y') => self.choose_link(true),
+            Key::Esc | Key::Char('q') => self.link_picker = None,
+            _ => (),
+        }
+    }
+
+    /// Closes the link picker, opening or copying the selected link
+    fn choose_link(&mut self, copy: bool) {
+        let Some(url) = self
+            .link_picker
+            .take()
+            .and_then(|picker| picker.selected_link().map(str::to_string))
+        else {
+            return;
+        };
+        match copy {
+            true => self.copy_link(&url),
+            false => self.open_link(&url),
+        }
+    }
+
+    /// Runs the configured opener, links are copied when there's none
+    pub fn open_link(&mut self, url: &str) {
+        if self.ui_config.links.opener.is_empty() {
+            self.copy_link(url);
+            return;
+        }
+        if let Err(err) = open_link(&self.ui_config.links.opener, url) {
+            self.prompt_error = Some(err.to_string());
+        }
+    }
+
+    fn copy_link(&mut self, url: &str) {
+        match copy_to_clipboard(url) {
+            Ok(()) => self.push_notice(Level::Info, &format!("Copied {}", url)),
+            Err(err) => self.prompt_error = Some(format!("Failed to copy link: {}", err)),
+        }
+    }
+
+    /// Starts selecting messages from the newest line
+    pub fn open_selection(&mut self) {
+        match self.chat.newest_id() {
+            Some(id) => self.selection = Some(Selection::new(id)),
+            None => self.prompt_error = Some(String::from("No messages to select")),
+        }
+    }
+
+    fn handle_selection_key(&mut self, key: KeyEvent, action: Option<Action>) {
+        let Some(selection) = &mut self.selection else {
+            return;
+        };
+        let cursor = selection.cursor;
+        let moved = match (action, key.code) {
+            (_, Key::Up | Key::Char('k')) => self.chat.find_older(Some(cursor), |_| true),
+            (_, Key::Down | Key::Char('j')) => self.chat.find_newer(cursor, |_| true),
+            (_, Key::Home | Key::Char('g')) => self.chat.oldest_id(),
+            (_, Key::End | Key::Char('G')) => self.chat.newest_id(),
+            (_, Key::Char('v') | Key::Char(' ')) => {
+                selection.toggle_range();
+                None
+            }
+            (_, Key::Enter | Key::Char('y')) => {
+                self.yank_selection(true);
+                None
+            }
+            (_, Key::Char('t')) => {
+                self.yank_selection(false);
+                None
+            }
+            (Some(Action::Select), _) | (_, Key::Esc | Key::Char('q')) => {
+                self.selection = None;
+                None
+            }
+            _ => None,
+        };
+        if let (Some(id), Some(selection)) = (moved, &mut self.selection) {
+            selection.cursor = id;
+            self.chat.jump_to(id);
+        }
+    }
+
+    /// Copies the selected lines and leaves the selection mode
+    fn yank_selection(&mut self, with_metadata: bool) {
+        let Some(selection) = self.selection.take() else {
+            return;
+        };
+        let lines: Vec<&ChatLine> = self.chat.lines_in(selection.range()).collect();
+        let text = yank_text(lines.iter().copied(), with_metadata);
+        let count = lines.len();
+        match copy_to_clipboard(&text) {
+            Ok(()) => self.push_notice(Level::Info, &format!("Copied {} lines", count)),
+            Err(err) => self.prompt_error = Some(format!("Failed to copy lines: {}", err)),
+        }
+    }
+
+    fn handle_layout_key(&mut self, key: KeyEvent, action: Option<Action>) {
+        let layout = &mut self.ui_config.layout;
+        match (action, key.code) {
+            (_, Key::Char('t')) => layout.toggle(Pane::Tabs),
+            (_, Key::Char('h')) => layout.toggle(Pane::Help),
+            (_, Key::Char('r')) => layout.toggle(Pane::Roster),
+            (_, Key::Char('l')) => layout.toggle(Pane::Logs),
+            (_, Key::Char('c')) => layout.compact = !layout.compact,
+            (_, Key::Left) => layout.resize_roster(2),
+            (_, Key::Right) => layout.resize_roster(-2),
+            (_, Key::Up) => layout.resize_logs(1),
+            (_, Key::Down) => layout.resize_logs(-1),
+            (Some(Action::Layout), _) | (_, Key::Esc | Key::Enter | Key::Char('q')) => {
+                self.layout_mode = false;
+                self.save_layout();
+            }
+            _ => (),
+        }
+        self.layout_changed();
+    }
+
     pub fn next_tab(&mut self) {
+        // Without the tabs pane a hidden Quit tab would swallow typing and quit on Enter
+        if !self.shows_tabs() {
+            return;
+        }
         self.selected_tab = (self.selected_tab + 1) % self.tab_names.len();
     }
 
+    pub fn read_to_bottom(&mut self) {
+        self.chat.scroll_to_bottom();
+        self.unread_mentions = 0;
+    }
+
+    fn can_complete(&self) -> bool {
+        self.completion.is_some() || !last_word(self.active_message.before_cursor()).1.is_empty()
+    }
+
+    /// Completes the username, command or emoji being typed,
+    /// cycling through candidates on repeat
+    pub fn complete(&mut self) {
+        match &mut self.completion {
+            Some(completion) => completion.next(),
+            None => self.completion = self.new_completion(),
+        }
+        if let Some(completion) = &self.completion {
+            completion.apply(&mut self.active_message);
+        }
+    }
+
+    fn new_completion(&self) -> Option<Completion> {
+        let before_cursor = self.active_message.before_cursor();
+        let (start, prefix) = last_word(before_cursor);
+        if before_cursor.starts_with('/') && !before_cursor.contains(' ') {
+            return Completion::new(0, complete_command(before_cursor));
+        }
+        if self.ui_config.emoji.shortcodes && prefix.starts_with(':') {
+            return Completion::new(start, complete_shortcode(prefix));
+        }
+        let candidates = self
+            .roster
+            .complete(prefix.trim_start_matches('@'))
+            .into_iter()
+            .filter(|user| *user != self.client_config.username)
+            .map(|user| match prefix.starts_with('@') {
+                true => format!("@{}", user),
+                false => user,
+            })
+            .collect();
+        Completion::new(start, candidates)
+    }
+
+    /// Addresses a user at the start of the prompt, or mentions them after existing text
+    pub fn insert_username(&mut self, username: &str) {
+        self.selected_tab = 0;
+        if self.active_message.is_empty() {
+            self.active_message.set(format!("{}: ", username));
+            return;
+        }
+        self.active_message.move_end();
+        if !self.active_message.text().ends_with(' ') {
+            self.active_message.insert(' ');
+        }
+        self.active_message.insert_str(username);
+        self.active_message.insert(' ');
+    }
+
+    /// Inserts pasted text into the prompt, several lines need confirmation to be sent
+    pub fn paste(&mut self, text: &str) {
+        let mut lines = paste_lines(text);
+        if lines.is_empty() {
+            return;
+        }
+        self.selected_tab = 0;
+        self.completion = None;
+        self.prompt_error = None;
+        if lines.len() == 1 {
+            self.active_message.insert_str(&lines[0]);
+            return;
+        }
+        lines[0].insert_str(0, &self.active_message.take());
+        self.pending_paste = Some(lines);
+    }
+
+    /// Sends a pending paste line by line on yes, keeps it in the prompt as one line on no
+    fn confirm_paste(&mut self, key: KeyEvent) {
+        let Some(lines) = self.pending_paste.take() else {
+            return;
+        };
+        match key.code {
+            Key::Enter | Key::Char('y') | Key::Char('Y') => {
+                self.read_to_bottom();
+                for line in lines {
+                    self.send_message(line);
+                }
+                self.rerender();
+            }
+            Key::Esc | Key::Char('n') | Key::Char('N') => self.active_message.set(lines.join(" ")),
+            _ => self.pending_paste = Some(lines),
+        }
+    }
+
+    /// Types a character at the cursor, a completed `:shortcode:` turns into its emoji
     pub fn add_active_message(&mut self, c: char) {
-        self.active_message.push(c);
+        self.active_message.insert(c);
+        if c != ':' || !self.ui_config.emoji.shortcodes {
+            return;
+        }
+        if let Some((start, emoji)) = trailing_shortcode(self.active_message.before_cursor()) {
+            self.active_message.replace_before_cursor(start, emoji);
+        }
     }
 
     pub fn backspace_active_message(&mut self) {
-        self.active_message.pop();
+        self.active_message.delete_backward();
     }
 
     pub fn trigger_quit(&mut self) {
+        // Nothing waits for a QUIT to be sent while replaying or without a connection
+        if self.replaying || self.status.connection != ConnectionState::Connected {
+            self.clear_title();
+            self.mode = AppMode::Quit;
+            return;
+        }
         self.send_payload(DspPayload {
             username: self.client_config.username.clone(),
             message: DspMessage::QuitMessage(QuitMessage {}),
         });
     }
 
+    /// Queues a payload, payloads are sent one at a time in order
     fn send_payload(&mut self, payload: DspPayload) {
+        if self.replaying {
+            self.push_notice(Level::Warn, "Nothing is sent while replaying a session");
+            return;
+        }
+        self.outbox.push_back(payload);
+        self.flush_outbox();
+    }
+
+    fn flush_outbox(&mut self) {
+        // Queued payloads wait for a connection instead of failing on a closed one
+        if self.outbox.is_empty() || self.status.connection != ConnectionState::Connected {
+            return;
+        }
+
+        // Writer is missing while another payload is in-transit,
+        // the outbox is flushed again once it's returned
         let ui_tx = self.app_event_tx.clone();
         let mut client_writer = match self.client_writer.try_lock() {
             Ok(mut maybe_writer) => match maybe_writer.take() {
                 Some(writer) => writer,
-                None => {
-                    error!(target: NS_CHAT, "Failed to send message, another message might already be in-transit");
-                    return;
-                }
+                None => return,
             },
-            Err(_) => {
-                error!(target: NS_CHAT, "Failed to send message, another message is about to possibly go in-transit");
-                return;
-            }
+            Err(_) => return,
+        };
+        let Some(payload) = self.outbox.pop_front() else {
+            return;
         };
+        if let DspMessage::MessageMessage(message) = &payload.message {
+            self.status.message_sent(&message.text);
+        }
 
+        let connection = self.connection;
         tokio::spawn(async move {
-            if let Some(err) = client_writer
+            let event = match client_writer
                 .write(payload.clone())
                 .await
-                .with_context(|| format!("Failed to send message to server"))
-                .err()
+                .context("Failed to send message to server")
             {
-                AppState::send_ui_fatal(ui_tx.clone(), err.to_string());
-            }
+                Ok(()) => AppEvent::PayloadSent((connection, client_writer, payload)),
+                Err(err) => AppEvent::SendFailed((connection, payload, format!("{:#}", err))),
+            };
             if let Some(err) = ui_tx
-                .send(AppEvent::PayloadSent((client_writer, payload)))
-                .with_context(|| format!("Failed to return DspWriter to UI"))
+                .send(event)
+                .context("Failed to return DspWriter to UI")
                 .err()
             {
                 AppState::send_ui_fatal(ui_tx.clone(), err.to_string());
@@ -230,28 +985,286 @@ impl AppState {
         });
     }
 
-    pub fn send_active_message(&mut self) {
+    pub fn send_message(&mut self, text: String) {
         let username = self.client_config.username.clone();
-        let text = self.active_message.clone();
-        if text.is_empty() {
-            error!(target: NS_CHAT, "Can't send empty message");
+        let message = DspMessage::MessageMessage(MessageMessage { text });
+        let payload = DspPayload { username, message };
+        self.send_payload(payload);
+    }
+
+    /// Sends the prompt contents as a message or runs it as a command
+    pub fn submit_active_message(&mut self) {
+        match parse_prompt(self.active_message.text()) {
+            Ok(PromptInput::Message(text)) => {
+                if text.is_empty() {
+                    self.push_notice(Level::Error, "Can't send empty message");
+                    return;
+                }
+                self.active_message.clear();
+                self.read_to_bottom();
+                self.send_message(text);
+            }
+            Ok(PromptInput::Command(command)) => {
+                self.active_message.clear();
+                self.chat.scroll_to_bottom();
+                self.run_command(command);
+            }
+            Err(error) => self.prompt_error = Some(error),
+        }
+    }
+
+    pub fn run_command(&mut self, command: Command) {
+        match command {
+            Command::Quit(reason) => {
+                if let Some(reason) = reason {
+                    self.send_message(reason);
+                }
+                self.trigger_quit();
+            }
+            Command::Clear => self.chat.clear(),
+            Command::Me(action) => {
+                let text = format!("* {} {}", self.client_config.username, action);
+                self.send_message(text);
+            }
+            Command::Ignore(None) => {
+                let ignore = &self.ui_config.filter.config().ignore;
+                let ignored = match ignore.is_empty() {
+                    true => String::from("nobody"),
+                    false => Vec::from_iter(ignore.iter().cloned()).join(", "),
+                };
+                self.push_notice(Level::Info, &format!("Ignoring {}", ignored));
+            }
+            Command::Ignore(Some(username)) => match self.ui_config.filter.ignore(&username) {
+                true => {
+                    self.push_notice(
+                        Level::Info,
+                        &format!("Ignoring messages from '{}'", username),
+                    );
+                    self.save_filters();
+                }
+                false => self.prompt_error = Some(format!("'{}' is already ignored", username)),
+            },
+            Command::Unignore(username) => match self.ui_config.filter.unignore(&username) {
+                true => {
+                    self.push_notice(
+                        Level::Info,
+                        &format!("No longer ignoring messages from '{}'", username),
+                    );
+                    self.save_filters();
+                }
+                false => self.prompt_error = Some(format!("'{}' isn't ignored", username)),
+            },
+            Command::Filter(None) => self.list_filters(),
+            Command::Filter(Some(pattern)) => match self.ui_config.filter.add_pattern(&pattern) {
+                Ok(()) => {
+                    self.push_notice(
+                        Level::Info,
+                        &format!("Hiding messages matching '{}'", pattern),
+                    );
+                    self.save_filters();
+                }
+                Err(error) => self.prompt_error = Some(error),
+            },
+            Command::Unfilter(pattern) => match self.ui_config.filter.remove_pattern(&pattern) {
+                true => {
+                    self.push_notice(Level::Info, &format!("Removed filter '{}'", pattern));
+                    self.save_filters();
+                }
+                false => self.prompt_error = Some(format!("No filter '{}'", pattern)),
+            },
+            Command::Joins(show) => {
+                self.ui_config.filter.set_hide_joins(!show);
+                let state = if show { "Showing" } else { "Hiding" };
+                self.push_notice(Level::Info, &format!("{} joins and quits", state));
+                self.save_filters();
+            }
+            Command::Collapse(collapse) => {
+                self.ui_config.filter.set_collapse_repeats(collapse);
+                let state = if collapse {
+                    "Collapsing"
+                } else {
+                    "Not collapsing"
+                };
+                self.push_notice(Level::Info, &format!("{} repeated messages", state));
+                self.save_filters();
+            }
+            Command::DoNotDisturb(enabled) => {
+                let enabled = enabled.unwrap_or(!self.notifier.do_not_disturb());
+                self.notifier.set_do_not_disturb(enabled);
+                let state = if enabled { "on" } else { "off" };
+                self.push_notice(Level::Info, &format!("Do not disturb is {}", state));
+            }
+            Command::Help => {
+                for spec in COMMANDS {
+                    let help = format!("{:<40} {}", spec.usage, spec.description);
+                    self.push_notice(Level::Info, &help);
+                }
+            }
+            Command::Reconnect => self.reconnect(),
+            Command::Export(path) => self.export_chat(path),
+            Command::Log(level) => {
+                tui_logger::set_default_level(level);
+                self.push_notice(Level::Info, &format!("Log level set to {}", level));
+            }
+            Command::Raw(frame) => match parse_payload(frame) {
+                Ok(payload) => self.send_payload(payload),
+                Err(err) => self.prompt_error = Some(format!("{:#}", err)),
+            },
+        }
+        self.rerender();
+    }
+
+    fn clear_title(&mut self) {
+        if let Err(err) = self.notifier.clear_title() {
+            warn!(target: NS_APP, "Failed to restore window title: {}", err);
+        }
+    }
+
+    fn notify(&mut self, event: NotifyEvent) {
+        if let Err(err) = self.notifier.notify(event) {
+            warn!(target: NS_APP, "Failed to notify: {:#}", err);
+        }
+    }
+
+    fn list_filters(&mut self) {
+        let config = self.ui_config.filter.config().clone();
+        let patterns = match config.patterns.is_empty() {
+            true => String::from("none"),
+            false => config.patterns.join(", "),
+        };
+        let on_off = |enabled: bool| if enabled { "on" } else { "off" };
+        let summary = format!(
+            "Filters: {} | Hide joins: {} | Collapse repeats: {} | Hidden so far: {}",
+            patterns,
+            on_off(config.hide_joins),
+            on_off(config.collapse_repeats),
+            self.status.hidden
+        );
+        self.push_notice(Level::Info, &summary);
+    }
+
+    /// Persists filters into the config file so they survive restarts
+    fn save_filters(&mut self) {
+        let Some(path) = self.ui_config.config_path.clone() else {
             return;
+        };
+        if let Err(err) = FileConfig::write_filters(&path, self.ui_config.filter.config()) {
+            self.push_notice(Level::Error, &format!("{:#}", err));
         }
-        let message = DspMessage::MessageMessage(MessageMessage { text: text.clone() });
-        let payload = DspPayload {
-            username: username.clone(),
-            message,
+    }
+
+    /// Persists the layout into the config file, so the next session starts with it
+    fn save_layout(&mut self) {
+        let Some(path) = self.ui_config.config_path.clone() else {
+            return;
         };
-        self.send_payload(payload);
+        if let Err(err) = FileConfig::write_layout(&path, &self.ui_config.layout) {
+            self.push_notice(Level::Error, &format!("{:#}", err));
+        }
     }
 
-    pub fn active_message_sent(&mut self, writer: DspWriter) {
+    fn export_chat(&mut self, path: Option<String>) {
+        let path = path
+            .unwrap_or_else(|| format!("uiop-chat-{}.log", Local::now().format("%Y%m%d-%H%M%S")));
+        let contents: String = self
+            .chat
+            .lines()
+            .map(|line| format!("{} {}\n", line.time.format("%Y-%m-%dT%H:%M:%S"), line))
+            .collect();
+        match fs::write(&path, contents) {
+            Ok(()) => self.push_notice(Level::Info, &format!("Chat exported to '{}'", path)),
+            Err(err) => self.push_notice(
+                Level::Error,
+                &format!("Failed to export chat to '{}': {}", path, err),
+            ),
+        }
+    }
+
+    /// Drops the current connection and joins the server again in the background
+    pub fn reconnect(&mut self) {
+        if self.replaying {
+            self.push_notice(
+                Level::Warn,
+                "There's no connection while replaying a session",
+            );
+            return;
+        }
+        if let Some(task) = self.receive_task.take() {
+            task.abort();
+        }
+        self.connection += 1;
+        if let Ok(mut parking) = self.client_writer.try_lock() {
+            parking.take();
+        }
+        let address = &self.client_config.server_address;
+        self.push_notice(Level::Info, &format!("Reconnecting to {}...", address));
+        self.status.connecting();
+
+        let config = self.client_config.clone();
+        let connection = self.connection;
+        let ui_tx = self.app_event_tx.clone();
+        tokio::spawn(async move {
+            match DspClient::start(&config).await {
+                Ok(client) => {
+                    let _ = ui_tx.send(AppEvent::Reconnected((connection, client)));
+                }
+                Err(err) => AppState::send_ui_disconnected(ui_tx, connection, format!("{:#}", err)),
+            }
+        });
+    }
+
+    fn reconnected(&mut self, connection: u64, client: DspClient) {
+        // A newer reconnect was started meanwhile
+        if connection != self.connection {
+            return;
+        }
+        self.park_writer(client.writer);
+        self.spawn_payload_receiver(client.reader);
+        self.status.connected();
+        self.push_notice(Level::Info, "Reconnected");
+        self.flush_outbox();
+        self.rerender();
+    }
+
+    fn disconnected(&mut self, error: String) {
+        error!(target: NS_APP, "{}", error);
+        self.status.disconnected(error.clone());
+        self.notify(NotifyEvent::Error(&error));
+        self.push_line(ChatLine::notice(Level::Error, error));
+        self.rerender();
+    }
+
+    /// Queues the payload again for the next connection, a failed write ends the current one
+    fn send_failed(&mut self, connection: u64, payload: DspPayload, error: String) {
+        if let DspMessage::QuitMessage(_) = payload.message {
+            self.clear_title();
+            self.mode = AppMode::Quit;
+            return;
+        }
+        self.outbox.push_front(payload);
+        if connection == self.connection {
+            if let Some(task) = self.receive_task.take() {
+                task.abort();
+            }
+            self.disconnected(error);
+        }
+        self.flush_outbox();
+    }
+
+    pub fn payload_sent(&mut self, connection: u64, writer: DspWriter) {
+        // Writers of connections dropped by a reconnect are closed instead of reused
+        if connection == self.connection {
+            self.park_writer(writer);
+        }
+        self.flush_outbox();
+    }
+
+    fn park_writer(&mut self, writer: DspWriter) {
         loop {
             match self.client_writer.try_lock() {
                 Ok(mut parking) => {
                     // Got access to mutex, left writer back in app
                     let _ = parking.insert(writer);
-                    self.active_message = String::from("");
                     return;
                 }
                 _ => {
@@ -264,6 +1277,19 @@ impl AppState {
     }
 }
 
+/// Rerenders every second to keep the connection time in the status bar ticking
+fn clock_thread(tx: Sender<AppEvent>) {
+    while tx.send(AppEvent::Rerender()).is_ok() {
+        thread::sleep(Duration::from_secs(1));
+    }
+}
+
+/// Plain characters are typed into the prompt, unbound shortcuts are ignored
+fn is_text_input(key: KeyEvent) -> bool {
+    !key.modifiers
+        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+}
+
 async fn payload_receive_task(
     mut reader: DspReader,
     tx: mpsc::Sender<AppEvent>,
@@ -271,6 +1297,6 @@ async fn payload_receive_task(
     loop {
         let payload = reader.read().await?;
         tx.send(AppEvent::PayloadReceived(payload))
-            .with_context(|| format!("Failed to send received DSP message to UI"))?;
+            .context("Failed to send received DSP message to UI")?;
     }
 }
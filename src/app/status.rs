@@ -0,0 +1,199 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use ratatui::{prelude::*, widgets::*};
+
+/// Amount of sent messages remembered while waiting for their echo
+const IN_FLIGHT_CAPACITY: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connecting,
+    Connected,
+    Disconnected,
+}
+
+/// Session info shown in the status bar
+#[derive(Debug)]
+pub struct SessionStatus {
+    pub connection: ConnectionState,
+    connected_at: Option<Instant>,
+    pub sent: usize,
+    pub received: usize,
+    pub last_error: Option<String>,
+    pub challenge_pending: bool,
+    /// Sent messages waiting for the server to echo them back
+    in_flight: VecDeque<(String, Instant)>,
+    pub round_trip: Option<Duration>,
+    /// Amount of payloads kept out of the chat by filters
+    pub hidden: usize,
+}
+
+impl SessionStatus {
+    pub fn new() -> SessionStatus {
+        SessionStatus {
+            connection: ConnectionState::Connected,
+            connected_at: Some(Instant::now()),
+            sent: 0,
+            received: 0,
+            last_error: None,
+            challenge_pending: false,
+            in_flight: VecDeque::new(),
+            round_trip: None,
+            hidden: 0,
+        }
+    }
+
+    pub fn connecting(&mut self) {
+        self.connection = ConnectionState::Connecting;
+        self.connected_at = None;
+    }
+
+    pub fn connected(&mut self) {
+        self.connection = ConnectionState::Connected;
+        self.connected_at = Some(Instant::now());
+        self.challenge_pending = false;
+        self.in_flight.clear();
+    }
+
+    pub fn disconnected(&mut self, error: String) {
+        self.connection = ConnectionState::Disconnected;
+        self.connected_at = None;
+        self.last_error = Some(error);
+    }
+
+    pub fn connected_for(&self) -> Option<Duration> {
+        self.connected_at.map(|at| at.elapsed())
+    }
+
+    pub fn message_sent(&mut self, text: &str) {
+        self.sent += 1;
+        if self.in_flight.len() >= IN_FLIGHT_CAPACITY {
+            self.in_flight.pop_front();
+        }
+        self.in_flight.push_back((text.to_string(), Instant::now()));
+    }
+
+    /// Measures the round-trip of one of our messages echoed back by the server,
+    /// older messages which were never echoed are forgotten
+    pub fn echo_received(&mut self, text: &str) {
+        let Some(index) = self.in_flight.iter().position(|(sent, _)| sent == text) else {
+            return;
+        };
+        let (_, sent_at) = self.in_flight[index];
+        self.in_flight.drain(..=index);
+        self.round_trip = Some(sent_at.elapsed());
+    }
+}
+
+impl Default for SessionStatus {
+    fn default() -> Self {
+        SessionStatus::new()
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Single line summary of a [`SessionStatus`]
+pub struct StatusBar<'a> {
+    status: &'a SessionStatus,
+    style: Style,
+    error_style: Style,
+}
+
+impl<'a> StatusBar<'a> {
+    pub fn new(status: &'a SessionStatus) -> Self {
+        StatusBar {
+            status,
+            style: Style::default(),
+            error_style: Style::default(),
+        }
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn error_style(mut self, style: Style) -> Self {
+        self.error_style = style;
+        self
+    }
+}
+
+impl Widget for StatusBar<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let status = self.status;
+        let connection = match (status.connection, status.connected_for()) {
+            (ConnectionState::Connected, Some(duration)) => {
+                Span::raw(format!("● Connected {}", format_duration(duration)))
+            }
+            (ConnectionState::Connected, None) => Span::raw("● Connected"),
+            (ConnectionState::Connecting, _) => Span::raw("◌ Connecting"),
+            (ConnectionState::Disconnected, _) => Span::styled("○ Disconnected", self.error_style),
+        };
+        let round_trip = match status.round_trip {
+            Some(round_trip) => format!("{} ms", round_trip.as_millis()),
+            None => String::from("-"),
+        };
+
+        let mut spans = vec![
+            Span::raw(" "),
+            connection,
+            Span::raw(format!(
+                " | Sent {} | Received {} | Echo {}",
+                status.sent, status.received, round_trip
+            )),
+        ];
+        if status.hidden > 0 {
+            spans.push(Span::raw(format!(" | Hidden {}", status.hidden)));
+        }
+        if status.challenge_pending {
+            spans.push(Span::raw(" | "));
+            spans.push(Span::styled("Challenge pending", self.error_style));
+        }
+        if let Some(error) = &status.last_error {
+            spans.push(Span::raw(" | "));
+            spans.push(Span::styled(format!("Error: {}", error), self.error_style));
+        }
+
+        Paragraph::new(Line::from(spans))
+            .style(self.style)
+            .render(area, buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_echo_round_trip() {
+        let mut status = SessionStatus::new();
+        status.message_sent("lost");
+        status.message_sent("hello");
+        status.message_sent("later");
+        status.echo_received("someone else's");
+        assert_eq!(status.round_trip, None);
+
+        status.echo_received("hello");
+        assert!(status.round_trip.is_some());
+        assert_eq!(status.in_flight.len(), 1);
+        assert_eq!(status.sent, 3);
+    }
+
+    #[test]
+    fn check_duration_format() {
+        assert_eq!(format_duration(Duration::from_secs(3725)), "01:02:05");
+    }
+}
@@ -0,0 +1,281 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use anyhow::{Result, anyhow};
+use ratatui::style::{Color, Style, Stylize};
+use serde::Deserialize;
+
+use super::chat::ChatStyles;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThemeName {
+    #[default]
+    Dark,
+    Light,
+    HighContrast,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsernameColors {
+    /// Usernames use the same colour as the message
+    #[default]
+    Plain,
+    /// Every user gets a stable colour derived from a hash of their username
+    Hash,
+}
+
+/// Styles of every part of the UI
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub chat: ChatStyles,
+    pub border: Style,
+    pub selected_tab: Style,
+    pub mention_badge: Style,
+    pub prompt_border: Style,
+    pub prompt_text: Style,
+    pub prompt_error: Style,
+    pub status: Style,
+    pub status_error: Style,
+    pub help: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::builtin(ThemeName::Dark)
+    }
+}
+
+impl Theme {
+    pub fn builtin(name: ThemeName) -> Theme {
+        match name {
+            ThemeName::Dark => Theme {
+                chat: ChatStyles {
+                    timestamp: Style::default().fg(Color::DarkGray),
+                    error: Style::default().fg(Color::Red).italic(),
+                    warn: Style::default().fg(Color::Yellow).italic(),
+                    info: Style::default().fg(Color::White),
+                    debug: Style::default().fg(Color::Gray).italic(),
+                    trace: Style::default().fg(Color::Gray).italic(),
+                    mention_line: Style::default().bold(),
+                    mention: Style::default().fg(Color::Yellow).reversed(),
+                    last_read: Style::default().fg(Color::LightRed),
+                    search_match: Style::default().fg(Color::Black).bg(Color::Cyan),
+                    search_current: Style::default().fg(Color::Black).bg(Color::LightYellow),
+                    link: Style::default().fg(Color::LightBlue).underlined(),
+                    selection: Style::default().bg(Color::DarkGray),
+                    sender_palette: vec![
+                        Color::LightCyan,
+                        Color::LightGreen,
+                        Color::LightMagenta,
+                        Color::LightBlue,
+                        Color::LightRed,
+                        Color::Cyan,
+                        Color::Green,
+                        Color::Magenta,
+                    ],
+                },
+                border: Style::default(),
+                selected_tab: Style::default().reversed(),
+                mention_badge: Style::default().fg(Color::Yellow).bold(),
+                prompt_border: Style::default().fg(Color::White),
+                prompt_text: Style::default().fg(Color::White),
+                prompt_error: Style::default().fg(Color::Red).italic(),
+                status: Style::default().fg(Color::White).bg(Color::DarkGray),
+                status_error: Style::default().fg(Color::LightRed).bold(),
+                help: Style::default().fg(Color::Gray),
+            },
+            ThemeName::Light => Theme {
+                chat: ChatStyles {
+                    timestamp: Style::default().fg(Color::Gray),
+                    error: Style::default().fg(Color::Red).italic(),
+                    warn: Style::default().fg(Color::Magenta).italic(),
+                    info: Style::default().fg(Color::Black),
+                    debug: Style::default().fg(Color::DarkGray).italic(),
+                    trace: Style::default().fg(Color::DarkGray).italic(),
+                    mention_line: Style::default().bold(),
+                    mention: Style::default().fg(Color::Blue).reversed(),
+                    last_read: Style::default().fg(Color::Red),
+                    search_match: Style::default().bg(Color::LightCyan),
+                    search_current: Style::default().bg(Color::Yellow),
+                    link: Style::default().fg(Color::Blue).underlined(),
+                    selection: Style::default().bg(Color::Gray),
+                    sender_palette: vec![
+                        Color::Blue,
+                        Color::Green,
+                        Color::Magenta,
+                        Color::Cyan,
+                        Color::Red,
+                        Color::Indexed(94),
+                        Color::Indexed(23),
+                        Color::Indexed(53),
+                    ],
+                },
+                border: Style::default().fg(Color::DarkGray),
+                selected_tab: Style::default().reversed(),
+                mention_badge: Style::default().fg(Color::Blue).bold(),
+                prompt_border: Style::default().fg(Color::Black),
+                prompt_text: Style::default().fg(Color::Black),
+                prompt_error: Style::default().fg(Color::Red).italic(),
+                status: Style::default().fg(Color::Black).bg(Color::Gray),
+                status_error: Style::default().fg(Color::Red).bold(),
+                help: Style::default().fg(Color::DarkGray),
+            },
+            ThemeName::HighContrast => Theme {
+                chat: ChatStyles {
+                    timestamp: Style::default().fg(Color::White),
+                    error: Style::default().fg(Color::LightRed).bold(),
+                    warn: Style::default().fg(Color::LightYellow).bold(),
+                    info: Style::default().fg(Color::White),
+                    debug: Style::default().fg(Color::White).italic(),
+                    trace: Style::default().fg(Color::White).italic(),
+                    mention_line: Style::default().bold().underlined(),
+                    mention: Style::default().fg(Color::Black).bg(Color::LightYellow),
+                    last_read: Style::default().fg(Color::LightRed).bold(),
+                    search_match: Style::default().fg(Color::Black).bg(Color::LightCyan),
+                    search_current: Style::default().fg(Color::Black).bg(Color::White).bold(),
+                    link: Style::default().fg(Color::LightCyan).underlined(),
+                    selection: Style::default().fg(Color::Black).bg(Color::LightBlue),
+                    sender_palette: vec![
+                        Color::LightCyan,
+                        Color::LightGreen,
+                        Color::LightMagenta,
+                        Color::LightYellow,
+                    ],
+                },
+                border: Style::default().fg(Color::White).bold(),
+                selected_tab: Style::default().fg(Color::Black).bg(Color::White).bold(),
+                mention_badge: Style::default().fg(Color::Black).bg(Color::LightYellow),
+                prompt_border: Style::default().fg(Color::White).bold(),
+                prompt_text: Style::default().fg(Color::White).bold(),
+                prompt_error: Style::default().fg(Color::LightRed).bold(),
+                status: Style::default().fg(Color::Black).bg(Color::White),
+                status_error: Style::default().fg(Color::Red).bold(),
+                help: Style::default().fg(Color::White),
+            },
+        }
+    }
+
+    /// Builds a theme from config, `no_color` drops all colours but keeps modifiers
+    pub fn new(config: &ThemeConfig, no_color: bool) -> Result<Theme> {
+        let mut theme = Theme::builtin(config.name);
+        for (slot, color) in &config.colors {
+            let color = Color::from_str(color)
+                .map_err(|_| anyhow!("Invalid colour '{}' for '{}'", color, slot))?;
+            let style = theme
+                .slot(slot)
+                .ok_or_else(|| anyhow!("Unknown theme colour '{}'", slot))?;
+            *style = style.fg(color);
+        }
+        if config.usernames == UsernameColors::Plain {
+            theme.chat.sender_palette.clear();
+        }
+        if config.monochrome.unwrap_or(no_color) {
+            theme = theme.monochrome();
+        }
+        Ok(theme)
+    }
+
+    fn slot(&mut self, name: &str) -> Option<&mut Style> {
+        Some(match name {
+            "timestamp" => &mut self.chat.timestamp,
+            "error" => &mut self.chat.error,
+            "warn" => &mut self.chat.warn,
+            "info" => &mut self.chat.info,
+            "debug" => &mut self.chat.debug,
+            "mention" => &mut self.chat.mention,
+            "last_read" => &mut self.chat.last_read,
+            "link" => &mut self.chat.link,
+            "border" => &mut self.border,
+            "prompt" => &mut self.prompt_text,
+            "prompt_border" => &mut self.prompt_border,
+            "status" => &mut self.status,
+            "help" => &mut self.help,
+            _ => return None,
+        })
+    }
+
+    pub fn monochrome(mut self) -> Theme {
+        let chat = &mut self.chat;
+        for style in [
+            &mut chat.timestamp,
+            &mut chat.error,
+            &mut chat.warn,
+            &mut chat.info,
+            &mut chat.debug,
+            &mut chat.trace,
+            &mut chat.mention_line,
+            &mut chat.mention,
+            &mut chat.last_read,
+            &mut chat.search_match,
+            &mut chat.search_current,
+            &mut chat.link,
+            &mut chat.selection,
+            &mut self.border,
+            &mut self.selected_tab,
+            &mut self.mention_badge,
+            &mut self.prompt_border,
+            &mut self.prompt_text,
+            &mut self.prompt_error,
+            &mut self.status,
+            &mut self.status_error,
+            &mut self.help,
+        ] {
+            style.fg = None;
+            style.bg = None;
+        }
+        // Mentions have to stand out without colours
+        chat.mention = chat.mention.reversed();
+        chat.search_match = chat.search_match.underlined();
+        chat.search_current = chat.search_current.reversed();
+        chat.selection = chat.selection.reversed();
+        chat.sender_palette.clear();
+        self.status = self.status.reversed();
+        self
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeConfig {
+    pub name: ThemeName,
+    pub usernames: UsernameColors,
+    /// Defaults to whether the `NO_COLOR` environment variable is set
+    pub monochrome: Option<bool>,
+    /// Foreground colour overrides, e.g. `error = "#ff5555"`
+    pub colors: BTreeMap<String, String>,
+}
+
+/// Stable across runs and platforms, unlike the std hasher
+pub fn username_hash(username: &str) -> u64 {
+    username.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_theme_config() {
+        let config = ThemeConfig {
+            colors: BTreeMap::from([(String::from("error"), String::from("#ff0000"))]),
+            usernames: UsernameColors::Hash,
+            ..ThemeConfig::default()
+        };
+        let theme = Theme::new(&config, false).unwrap();
+        assert_eq!(theme.chat.error.fg, Some(Color::Rgb(255, 0, 0)));
+        assert!(!theme.chat.sender_palette.is_empty());
+
+        let theme = Theme::new(&config, true).unwrap();
+        assert_eq!(theme.chat.error.fg, None);
+        assert!(theme.chat.sender_palette.is_empty());
+
+        let config = ThemeConfig {
+            colors: BTreeMap::from([(String::from("nope"), String::from("red"))]),
+            ..ThemeConfig::default()
+        };
+        assert!(Theme::new(&config, false).is_err());
+    }
+}
@@ -1,16 +1,167 @@
 use clap::Parser;
+use std::time::Duration;
 
-/// Simple program to greet a person
+use crate::{bench::BenchConfig, server::ServerConfig};
+
+/// Terminal chat client for DSP servers
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct Args {
-    /// Name of the person to greet
+    /// Address of the DSP server to connect to
     #[arg(short, long, default_value_t = String::from("185.216.203.250:1337"))]
     pub server_address: String,
 
+    /// Username to join the chat with
     #[arg(short, long)]
     pub username: String,
 
+    /// File to write the log to, besides the log view in the terminal
     #[arg(short, long)]
     pub log_file: Option<String>,
+
+    /// Path to a TOML config file, defaults to `uiop-chat/config.toml` in the user config dir
+    #[arg(short, long)]
+    pub config: Option<String>,
+
+    /// Leave mouse events to the terminal, e.g. to select and copy text
+    #[arg(long)]
+    pub no_mouse: bool,
+
+    /// Additional word which highlights incoming messages, can be repeated
+    #[arg(long = "highlight")]
+    pub highlight_words: Vec<String>,
+
+    /// JSONL capture file to record every sent and received frame to
+    #[arg(long)]
+    pub record: Option<String>,
+
+    /// Play a recorded session back instead of connecting to the server
+    #[arg(long, conflicts_with = "record")]
+    pub replay: Option<String>,
+
+    /// How many times faster than recorded to play a session back
+    #[arg(long, default_value_t = 1.0, requires = "replay")]
+    pub replay_speed: f64,
+
+    /// Neither load earlier messages nor store this session in the local history
+    #[arg(long)]
+    pub no_history: bool,
+}
+
+/// Reference DSP chat server
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct ServerArgs {
+    /// Address to accept clients on
+    #[arg(short, long, default_value_t = String::from("127.0.0.1:1337"))]
+    pub listen_address: String,
+
+    /// Messages a client can send at once before being challenged
+    #[arg(long, default_value_t = ServerConfig::default().burst)]
+    pub burst: u32,
+
+    /// Messages per second a client can keep sending
+    #[arg(long, default_value_t = ServerConfig::default().rate)]
+    pub rate: f64,
+
+    /// Leading zeros of the first challenge, each further one needs another zero
+    #[arg(long, default_value_t = ServerConfig::default().difficulty, value_parser = clap::value_parser!(u64).range(1..=64))]
+    pub difficulty: u64,
+
+    /// Leading zeros of the hardest challenge
+    #[arg(long, default_value_t = ServerConfig::default().max_difficulty, value_parser = clap::value_parser!(u64).range(1..=64))]
+    pub max_difficulty: u64,
+
+    /// Seconds to respond to a challenge before being disconnected
+    #[arg(long, default_value_t = ServerConfig::default().challenge_timeout.as_secs())]
+    pub challenge_timeout: u64,
+
+    /// Messages queued for a client before it's disconnected for not keeping up
+    #[arg(long, default_value_t = ServerConfig::default().outbox_capacity)]
+    pub outbox_capacity: usize,
+}
+
+impl ServerArgs {
+    pub fn config(&self) -> ServerConfig {
+        ServerConfig {
+            burst: self.burst,
+            rate: self.rate,
+            difficulty: self.difficulty,
+            max_difficulty: self.max_difficulty.max(self.difficulty),
+            challenge_timeout: Duration::from_secs(self.challenge_timeout),
+            outbox_capacity: self.outbox_capacity.max(1),
+        }
+    }
+}
+
+/// Proxy which captures the DSP traffic between clients and a server
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct ProxyArgs {
+    /// Address to accept clients on
+    #[arg(short, long, default_value_t = String::from("127.0.0.1:1338"))]
+    pub listen_address: String,
+
+    /// Server to forward clients to
+    #[arg(short, long, default_value_t = String::from("127.0.0.1:1337"))]
+    pub server_address: String,
+
+    /// JSONL file to write every frame to
+    #[arg(short, long)]
+    pub capture: Option<String>,
+
+    /// TOML file of rules delaying, dropping or rewriting frames
+    #[arg(short, long)]
+    pub rules: Option<String>,
+}
+
+/// Load test which measures how fast messages fan out to many concurrent clients
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct BenchArgs {
+    /// Server to benchmark
+    #[arg(short, long, default_value_t = String::from("127.0.0.1:1337"))]
+    pub server_address: String,
+
+    /// Start the reference server in-process with its default limits instead
+    #[arg(long, conflicts_with = "server_address")]
+    pub local: bool,
+
+    /// Concurrent clients
+    #[arg(short, long, default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+    pub clients: u64,
+
+    /// Messages per second each client sends
+    #[arg(short, long, default_value_t = 1.0)]
+    pub rate: f64,
+
+    /// Seconds to keep sending for
+    #[arg(short, long, default_value_t = 10)]
+    pub duration: u64,
+
+    /// Seconds to wait for messages in flight once sending stops
+    #[arg(long, default_value_t = 2)]
+    pub drain: u64,
+
+    /// Characters of every message
+    #[arg(short, long, default_value_t = 64, value_parser = clap::value_parser!(u64).range(1..=512))]
+    pub message_length: u64,
+
+    /// Clients join as the prefix followed by `_` and their number
+    #[arg(short, long, default_value_t = String::from("bench"))]
+    pub username_prefix: String,
+}
+
+impl BenchArgs {
+    pub fn config(&self) -> BenchConfig {
+        BenchConfig {
+            server_address: self.server_address.clone(),
+            clients: self.clients as usize,
+            username_prefix: self.username_prefix.clone(),
+            rate: self.rate,
+            duration: Duration::from_secs(self.duration),
+            drain: Duration::from_secs(self.drain),
+            message_length: self.message_length as usize,
+        }
+    }
 }
@@ -0,0 +1,410 @@
+use crate::{
+    client::{DspClient, DspReader, DspWriter},
+    config::DspClientConfig,
+    logger::NS_BENCH,
+    protocol::*,
+    server::solve_challenge,
+};
+
+use anyhow::{Context, Result, anyhow, bail, ensure};
+use log::{debug, warn};
+use std::{fmt, sync::Arc, time::Duration};
+use tokio::{
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    task::{JoinSet, spawn_blocking},
+    time::{Instant, MissedTickBehavior, interval_at, timeout, timeout_at},
+};
+
+/// How long a client may take to connect and see its own JOIN
+const JOIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone)]
+pub struct BenchConfig {
+    pub server_address: String,
+    /// Concurrent sessions, joined as `<prefix>_1`, `<prefix>_2`...
+    pub clients: usize,
+    pub username_prefix: String,
+    /// Messages per second each client sends
+    pub rate: f64,
+    /// How long clients keep sending
+    pub duration: Duration,
+    /// How long to wait for messages in flight once sending stops
+    pub drain: Duration,
+    /// Characters of every message text, padded after its sequence number and timestamp
+    pub message_length: usize,
+}
+
+/// Totals of a benchmark run across every client
+#[derive(Debug, Default)]
+pub struct BenchReport {
+    /// Clients which joined and took part
+    pub clients: usize,
+    /// Clients which failed to connect or join
+    pub failed_clients: usize,
+    pub duration: Duration,
+    /// MESSAGEs written by all clients
+    pub sent: u64,
+    /// MESSAGEs received by all clients, each is sent to every client
+    pub delivered: u64,
+    /// Send to receive time of every delivery, sorted
+    pub latencies: Vec<Duration>,
+    /// ERRORs received
+    pub errors: u64,
+    pub challenges: u64,
+    /// RESPONSEs written to challenges
+    pub responses: u64,
+    /// Clients which lost their connection before the end
+    pub disconnects: u64,
+}
+
+impl BenchReport {
+    /// Deliveries of every message sent to every client
+    pub fn expected(&self) -> u64 {
+        self.sent * self.clients as u64
+    }
+
+    /// Nearest rank percentile of the latencies, from 0 to 100
+    pub fn percentile(&self, percent: f64) -> Option<Duration> {
+        let rank = (percent / 100.0 * self.latencies.len() as f64).ceil() as usize;
+        let index = rank.clamp(1, self.latencies.len().max(1)) - 1;
+        self.latencies.get(index).copied()
+    }
+
+    fn per_second(&self, count: u64) -> f64 {
+        count as f64 / self.duration.as_secs_f64()
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let expected = self.expected().max(1) as f64;
+        writeln!(
+            f,
+            "Clients     {} joined, {} failed",
+            self.clients, self.failed_clients
+        )?;
+        writeln!(
+            f,
+            "Sent        {} messages, {:.1}/s",
+            self.sent,
+            self.per_second(self.sent)
+        )?;
+        writeln!(
+            f,
+            "Delivered   {} of {} ({:.2}%), {:.1}/s",
+            self.delivered,
+            self.expected(),
+            self.delivered as f64 / expected * 100.0,
+            self.per_second(self.delivered)
+        )?;
+        match self.percentile(50.0) {
+            Some(p50) => writeln!(
+                f,
+                "Latency     p50 {:.2?}, p90 {:.2?}, p99 {:.2?}, max {:.2?}",
+                p50,
+                self.percentile(90.0).unwrap(),
+                self.percentile(99.0).unwrap(),
+                self.latencies.last().unwrap()
+            )?,
+            None => writeln!(f, "Latency     nothing delivered")?,
+        }
+        writeln!(
+            f,
+            "Errors      {} received ({:.2}% of sent), {} disconnects",
+            self.errors,
+            self.errors as f64 / self.sent.max(1) as f64 * 100.0,
+            self.disconnects
+        )?;
+        write!(
+            f,
+            "Challenges  {} received ({:.2}% of sent), {} responded",
+            self.challenges,
+            self.challenges as f64 / self.sent.max(1) as f64 * 100.0,
+            self.responses
+        )
+    }
+}
+
+/// Connects every client, sends for the configured duration and waits for the messages in flight
+pub async fn run(config: &BenchConfig) -> Result<BenchReport> {
+    ensure!(config.rate > 0.0, "The message rate has to be positive");
+    let config = Arc::new(config.clone());
+
+    let mut joining = JoinSet::new();
+    for index in 0..config.clients {
+        let username = format!("{}_{}", config.username_prefix, index + 1);
+        let address = config.server_address.clone();
+        joining.spawn(async move { (connect(&address, &username).await, username) });
+    }
+    let mut report = BenchReport::default();
+    let mut clients = vec![];
+    while let Some(joined) = joining.join_next().await {
+        match joined.context("Failed to join a client task")? {
+            (Ok(client), username) => clients.push((client, username)),
+            (Err(err), _) => {
+                warn!(target: NS_BENCH, "{:#}", err);
+                report.failed_clients += 1;
+            }
+        }
+    }
+    if clients.is_empty() {
+        bail!("None of the {} clients could join", config.clients);
+    }
+
+    let start = Instant::now();
+    report.clients = clients.len();
+    report.duration = config.duration;
+    let mut sessions = JoinSet::new();
+    for (index, (client, username)) in clients.into_iter().enumerate() {
+        let session = Session {
+            index,
+            clients: report.clients,
+            username,
+            config: config.clone(),
+            start,
+        };
+        sessions.spawn(session.run(client));
+    }
+    while let Some(stats) = sessions.join_next().await {
+        let stats = stats.context("Failed to join a client task")?;
+        report.sent += stats.sent;
+        report.delivered += stats.latencies.len() as u64;
+        report.latencies.extend(stats.latencies);
+        report.errors += stats.errors;
+        report.challenges += stats.challenges;
+        report.responses += stats.responses;
+        report.disconnects += stats.disconnected as u64;
+    }
+    report.latencies.sort_unstable();
+    Ok(report)
+}
+
+/// Joins and waits until the server broadcasts the JOIN back, so no message is missed after
+async fn connect(address: &str, username: &str) -> Result<DspClient> {
+    let config = DspClientConfig {
+        server_address: address.to_string(),
+        username: username.to_string(),
+        recorder: None,
+        history: None,
+    };
+    timeout(JOIN_TIMEOUT, async {
+        let mut client = DspClient::start(&config).await?;
+        loop {
+            let payload = client.reader.read().await?;
+            match payload.message {
+                DspMessage::JoinMessage(_) if payload.username == username => return Ok(client),
+                DspMessage::ErrorMessage(error) => {
+                    bail!("Server refused '{}' to join: {}", username, error.text)
+                }
+                _ => {}
+            }
+        }
+    })
+    .await
+    .map_err(|_| anyhow!("Timed out joining as '{}'", username))?
+}
+
+#[derive(Default)]
+struct SessionStats {
+    sent: u64,
+    latencies: Vec<Duration>,
+    errors: u64,
+    challenges: u64,
+    responses: u64,
+    disconnected: bool,
+}
+
+#[derive(Clone)]
+struct Session {
+    index: usize,
+    clients: usize,
+    username: String,
+    config: Arc<BenchConfig>,
+    /// Timestamps in messages count from here, as every client shares the clock
+    start: Instant,
+}
+
+impl Session {
+    async fn run(self, client: DspClient) -> SessionStats {
+        let (outbox, queue) = mpsc::unbounded_channel();
+        let writer = tokio::spawn(write_queued(client.writer, queue));
+        let sender = tokio::spawn(self.clone().send_messages(outbox.clone()));
+        let mut stats = self.receive(client.reader, &outbox).await;
+        sender.abort();
+
+        // Quitting also stops the writer, which outlives challenges still being solved
+        let _ = outbox.send(self.payload(DspMessage::QuitMessage(QuitMessage {})));
+        if let Ok((sent, responses)) = writer.await {
+            stats.sent = sent;
+            stats.responses = responses;
+        }
+        stats
+    }
+
+    /// Queues messages at the configured rate, clients are spread across each period
+    async fn send_messages(self, outbox: UnboundedSender<DspPayload>) {
+        let period = Duration::from_secs_f64(1.0 / self.config.rate);
+        let offset = period.mul_f64(self.index as f64 / self.clients as f64);
+        let stop = self.start + self.config.duration;
+        let mut ticks = interval_at(self.start + offset, period);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        for sequence in 1.. {
+            if ticks.tick().await >= stop {
+                return;
+            }
+            let micros = self.start.elapsed().as_micros();
+            let mut text = format!("{} {} ", sequence, micros);
+            while text.len() < self.config.message_length {
+                text.push('x');
+            }
+            let message = DspMessage::MessageMessage(MessageMessage { text });
+            if outbox.send(self.payload(message)).is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Reads until the drain time after sending stops, or until the connection is lost
+    async fn receive(
+        &self,
+        mut reader: DspReader,
+        outbox: &UnboundedSender<DspPayload>,
+    ) -> SessionStats {
+        let end = self.start + self.config.duration + self.config.drain;
+        let mut stats = SessionStats::default();
+        loop {
+            let payload = match timeout_at(end, reader.read()).await {
+                Ok(Ok(payload)) => payload,
+                Ok(Err(err)) => {
+                    warn!(target: NS_BENCH, "{} disconnected: {:#}", self.username, err);
+                    stats.disconnected = true;
+                    return stats;
+                }
+                Err(_) => return stats,
+            };
+            match payload.message {
+                DspMessage::MessageMessage(message) => {
+                    if let Some(sent) = self.sent_at(&payload.username, &message.text) {
+                        stats
+                            .latencies
+                            .push(self.start.elapsed().saturating_sub(sent));
+                    }
+                }
+                DspMessage::ErrorMessage(error) => {
+                    debug!(target: NS_BENCH, "{} received ERROR {}", self.username, error.text);
+                    stats.errors += 1;
+                }
+                DspMessage::ChallengeMessage(challenge) => {
+                    stats.challenges += 1;
+                    self.respond(challenge, outbox.clone());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Solves a challenge off the runtime, hashes longer than SHA-256 are a ban rather than a challenge
+    fn respond(&self, challenge: ChallengeMessage, outbox: UnboundedSender<DspPayload>) {
+        debug!(target: NS_BENCH, "{} challenged with {} zeros", self.username, challenge.n);
+        if challenge.n > 64 {
+            warn!(target: NS_BENCH, "{} was banned by the server", self.username);
+            return;
+        }
+        let username = self.username.clone();
+        spawn_blocking(move || {
+            let phrase = solve_challenge(&challenge);
+            let _ = outbox.send(DspPayload {
+                username,
+                message: DspMessage::ResponseMessage(ResponseMessage { phrase }),
+            });
+        });
+    }
+
+    /// Send time of a message from any benchmark client
+    fn sent_at(&self, username: &str, text: &str) -> Option<Duration> {
+        let prefix = format!("{}_", self.config.username_prefix);
+        if !username.starts_with(&prefix) {
+            return None;
+        }
+        let micros = text.split(' ').nth(1)?.parse().ok()?;
+        Some(Duration::from_micros(micros))
+    }
+
+    fn payload(&self, message: DspMessage) -> DspPayload {
+        DspPayload {
+            username: self.username.clone(),
+            message,
+        }
+    }
+}
+
+/// Writes queued payloads until QUIT, counting the MESSAGEs and RESPONSEs written
+async fn write_queued(
+    mut writer: DspWriter,
+    mut queue: UnboundedReceiver<DspPayload>,
+) -> (u64, u64) {
+    let (mut sent, mut responses) = (0, 0);
+    while let Some(payload) = queue.recv().await {
+        let message_type = payload.message.message_type();
+        if let Err(err) = writer.write(payload).await {
+            debug!(target: NS_BENCH, "{:#}", err);
+            break;
+        }
+        match message_type {
+            MessageType::MESSAGE => sent += 1,
+            MessageType::RESPONSE => responses += 1,
+            MessageType::QUIT => break,
+            _ => {}
+        }
+    }
+    (sent, responses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{DspServer, ServerConfig};
+
+    async fn bench(server_config: ServerConfig, rate: f64) -> BenchReport {
+        let server = DspServer::bind("127.0.0.1:0", server_config).await.unwrap();
+        let server_address = server.local_addr().unwrap().to_string();
+        tokio::spawn(server.run());
+        run(&BenchConfig {
+            server_address,
+            clients: 3,
+            username_prefix: String::from("bench"),
+            rate,
+            duration: Duration::from_millis(600),
+            drain: Duration::from_millis(400),
+            message_length: 32,
+        })
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn check_bench_delivery() {
+        let report = bench(ServerConfig::default(), 5.0).await;
+        assert_eq!(report.clients, 3);
+        assert!(report.sent >= 6, "{}", report);
+        assert_eq!(report.delivered, report.expected(), "{}", report);
+        assert_eq!((report.errors, report.challenges), (0, 0));
+        assert!(report.percentile(50.0) <= report.percentile(99.0));
+    }
+
+    #[tokio::test]
+    async fn check_bench_challenges() {
+        let server_config = ServerConfig {
+            burst: 2,
+            rate: 1.0,
+            difficulty: 1,
+            max_difficulty: 1,
+            ..ServerConfig::default()
+        };
+        let report = bench(server_config, 20.0).await;
+        assert!(report.challenges > 0, "{}", report);
+        assert_eq!(report.responses, report.challenges, "{}", report);
+        assert_eq!(report.disconnects, 0);
+        assert!(report.delivered < report.expected());
+    }
+}
@@ -0,0 +1,39 @@
+use anyhow::{Context, Result};
+use clap::Parser;
+use log::info;
+use std::error::Error;
+use uiop_dsp::args::BenchArgs;
+use uiop_dsp::bench;
+use uiop_dsp::logger::{NS_BENCH, init_bench_logger};
+use uiop_dsp::server::{DspServer, ServerConfig};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    // Parse CLI args
+    let args = BenchArgs::try_parse().context("Invalid CLI arguments passed")?;
+
+    // Init logger
+    init_bench_logger()?;
+
+    // Build config, pointing at an in-process server when asked to
+    let mut config = args.config();
+    if args.local {
+        let server = DspServer::bind("127.0.0.1:0", ServerConfig::default()).await?;
+        config.server_address = server.local_addr()?.to_string();
+        info!(target: NS_BENCH, "Reference server listening on {}", config.server_address);
+        tokio::spawn(server.run());
+    }
+
+    // Load the server and report what the clients saw
+    info!(
+        target: NS_BENCH,
+        "Running {} clients at {} messages per second each for {}s",
+        config.clients,
+        config.rate,
+        config.duration.as_secs()
+    );
+    let report = bench::run(&config).await?;
+    println!("{}", report);
+
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use anyhow::{Context, Result};
+use clap::Parser;
+use log::info;
+use std::{error::Error, path::Path};
+use uiop_dsp::args::ProxyArgs;
+use uiop_dsp::capture::Recorder;
+use uiop_dsp::logger::{NS_PROXY, init_stderr_logger};
+use uiop_dsp::proxy::{DspProxy, ProxyConfig, ProxyRules};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    // Parse CLI args
+    let args = ProxyArgs::try_parse().context("Invalid CLI arguments passed")?;
+
+    // Init logger
+    init_stderr_logger()?;
+
+    // Build config
+    let rules = match &args.rules {
+        Some(path) => ProxyRules::read(Path::new(path))?,
+        None => ProxyRules::default(),
+    };
+    let capture = match &args.capture {
+        Some(path) => Some(Recorder::create(Path::new(path))?),
+        None => None,
+    };
+    let config = ProxyConfig {
+        server_address: args.server_address,
+        rules,
+        capture,
+    };
+
+    // Forward clients until the listener fails
+    let proxy = DspProxy::bind(&args.listen_address, config).await?;
+    info!(target: NS_PROXY, "Listening on {}", proxy.local_addr()?);
+    proxy.run().await?;
+
+    Ok(())
+}
@@ -0,0 +1,23 @@
+use anyhow::{Context, Result};
+use clap::Parser;
+use log::info;
+use std::error::Error;
+use uiop_dsp::args::ServerArgs;
+use uiop_dsp::logger::{NS_SERVER, init_stderr_logger};
+use uiop_dsp::server::DspServer;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    // Parse CLI args
+    let args = ServerArgs::try_parse().context("Invalid CLI arguments passed")?;
+
+    // Init logger
+    init_stderr_logger()?;
+
+    // Serve clients until the listener fails
+    let server = DspServer::bind(&args.listen_address, args.config()).await?;
+    info!(target: NS_SERVER, "Listening on {}", server.local_addr()?);
+    server.run().await?;
+
+    Ok(())
+}
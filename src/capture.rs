@@ -0,0 +1,165 @@
+use crate::{codec::parse_payload, protocol::DspPayload};
+
+use anyhow::{Context, Result};
+use chrono::{Local, SecondsFormat};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+/// Which way a captured frame went
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    ToServer,
+    ToClient,
+}
+
+/// A single line of a JSONL capture file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaptureRecord {
+    /// RFC 3339 local time with milliseconds
+    pub time: String,
+    /// Proxied connection the frame belongs to, counted from 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection: Option<u64>,
+    pub direction: Direction,
+    /// Frame text without its terminator
+    pub frame: String,
+    /// Decoded frame, `null` when it doesn't parse
+    pub payload: Option<DspPayload>,
+    /// Held back by proxy rules before forwarding
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub delay_ms: u64,
+    /// Not forwarded because of a proxy rule
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub dropped: bool,
+    /// Frame forwarded instead, after proxy rules rewrote it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rewritten: Option<String>,
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+impl CaptureRecord {
+    /// Record of a frame seen now, decoded if it parses
+    pub fn new(direction: Direction, frame: String) -> CaptureRecord {
+        let payload = parse_payload(frame.clone()).ok();
+        CaptureRecord::parsed(direction, frame, payload)
+    }
+
+    /// Record of a frame seen now which was already decoded
+    pub fn parsed(
+        direction: Direction,
+        frame: String,
+        payload: Option<DspPayload>,
+    ) -> CaptureRecord {
+        CaptureRecord {
+            time: Local::now().to_rfc3339_opts(SecondsFormat::Millis, false),
+            connection: None,
+            direction,
+            payload,
+            frame,
+            delay_ms: 0,
+            dropped: false,
+            rewritten: None,
+        }
+    }
+}
+
+/// Appends records to a capture file, one JSON object per line
+pub struct CaptureWriter {
+    file: BufWriter<File>,
+}
+
+impl CaptureWriter {
+    pub fn create(path: &Path) -> Result<CaptureWriter> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create capture file '{}'", path.display()))?;
+        Ok(CaptureWriter {
+            file: BufWriter::new(file),
+        })
+    }
+
+    /// Writes a record and flushes it, so captures survive crashes
+    pub fn write(&mut self, record: &CaptureRecord) -> Result<()> {
+        serde_json::to_writer(&mut self.file, record).context("Failed to encode capture record")?;
+        self.file
+            .write_all(b"\n")
+            .and_then(|_| self.file.flush())
+            .context("Failed to write capture record")
+    }
+}
+
+/// Capture file shared by every connection or half of one which is recorded
+#[derive(Clone)]
+pub struct Recorder {
+    capture: Arc<Mutex<CaptureWriter>>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> Result<Recorder> {
+        Ok(Recorder {
+            capture: Arc::new(Mutex::new(CaptureWriter::create(path)?)),
+        })
+    }
+
+    pub fn write(&self, record: &CaptureRecord) -> Result<()> {
+        self.capture.lock().unwrap().write(record)
+    }
+}
+
+pub fn read_capture(path: &Path) -> Result<Vec<CaptureRecord>> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read capture file '{}'", path.display()))?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).with_context(|| {
+                format!(
+                    "Invalid record on line {} of '{}'",
+                    index + 1,
+                    path.display()
+                )
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_record_format() {
+        let mut record = CaptureRecord::new(Direction::ToClient, String::from("bob MESSAGE hi"));
+        record.time = String::from("2025-04-01T12:00:00.000+03:00");
+        assert_eq!(
+            serde_json::to_string(&record).unwrap(),
+            r#"{"time":"2025-04-01T12:00:00.000+03:00","direction":"to_client","frame":"bob MESSAGE hi","payload":{"username":"bob","message":{"type":"MESSAGE","text":"hi"}}}"#
+        );
+
+        let record = CaptureRecord {
+            connection: Some(2),
+            dropped: true,
+            ..CaptureRecord::new(Direction::ToServer, String::from("not a payload"))
+        };
+        let line = serde_json::to_string(&record).unwrap();
+        assert!(line.contains(r#""connection":2,"#));
+        assert!(line.contains(r#""payload":null,"dropped":true}"#));
+        assert_eq!(
+            serde_json::from_str::<CaptureRecord>(&line).unwrap(),
+            record
+        );
+    }
+}
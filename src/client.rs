@@ -1,13 +1,16 @@
 use crate::{
-    codec::{payload_bytes, read_buffer_until_payload},
+    capture::{CaptureRecord, Direction, Recorder},
+    codec::{encode_payload, parse_text, read_text},
     config::DspClientConfig,
+    history::History,
     logger::NS_CONN,
     protocol::{DspMessage, *},
 };
 
 use anyhow::{Context, Result};
-use log::debug;
-use std::collections::VecDeque;
+use bytes::{BufMut, BytesMut};
+use log::{debug, warn};
+use std::str;
 use tokio::{
     io::{AsyncWriteExt, BufReader},
     net::{
@@ -16,33 +19,118 @@ use tokio::{
     },
 };
 
+/// Failing to record a frame is only logged, it never breaks the session
+fn record(recorder: &Option<Recorder>, direction: Direction, frame: &str) {
+    let Some(recorder) = recorder else {
+        return;
+    };
+    if let Err(err) = recorder.write(&CaptureRecord::new(direction, frame.to_string())) {
+        warn!(target: NS_CONN, "{:#}", err);
+    }
+}
+
 pub struct DspReader {
     underlying: BufReader<OwnedReadHalf>,
+    /// Reused for every frame, payloads are parsed from it before being copied out
+    frame: Vec<u8>,
+    recorder: Option<Recorder>,
+    history: Option<History>,
 }
 
 impl DspReader {
+    pub fn new(reader: OwnedReadHalf) -> DspReader {
+        DspReader {
+            underlying: BufReader::new(reader),
+            frame: vec![],
+            recorder: None,
+            history: None,
+        }
+    }
+
+    /// Records every frame read to a capture file
+    pub fn recording(mut self, recorder: Option<Recorder>) -> DspReader {
+        self.recorder = recorder;
+        self
+    }
+
+    /// Stores every payload read in the server's history
+    pub fn history(mut self, history: Option<History>) -> DspReader {
+        self.history = history;
+        self
+    }
+
+    /// Reads the next payload, frames which aren't UTF-8 are skipped
     pub async fn read(&mut self) -> Result<DspPayload> {
-        read_buffer_until_payload(&mut self.underlying).await
+        // Same as codec::read_payload, with the text recorded even if it doesn't parse
+        let text = read_text(&mut self.underlying, &mut self.frame).await?;
+        record(&self.recorder, Direction::ToClient, text);
+        let payload = parse_text(text)
+            .map(|payload| payload.to_payload())
+            .context("Failed to parse UTF-8 byte message as a DSP message payload")?;
+        if let Some(history) = &self.history {
+            history.append(Direction::ToClient, text, &payload);
+        }
+        Ok(payload)
     }
 }
 
 pub struct DspWriter {
     underlying: OwnedWriteHalf,
+    /// Reused for every frame, so writing doesn't allocate once it has grown
+    buffer: BytesMut,
+    recorder: Option<Recorder>,
+    history: Option<History>,
 }
 
 impl DspWriter {
+    pub fn new(writer: OwnedWriteHalf) -> DspWriter {
+        DspWriter {
+            underlying: writer,
+            buffer: BytesMut::new(),
+            recorder: None,
+            history: None,
+        }
+    }
+
+    /// Records every frame written to a capture file
+    pub fn recording(mut self, recorder: Option<Recorder>) -> DspWriter {
+        self.recorder = recorder;
+        self
+    }
+
+    /// Stores every payload written in the server's history
+    pub fn history(mut self, history: Option<History>) -> DspWriter {
+        self.history = history;
+        self
+    }
+
     pub async fn write(&mut self, payload: DspPayload) -> Result<()> {
-        let mut bytes = payload_bytes(payload);
-        bytes.push(0u8);
-        let mut deq = VecDeque::from(bytes);
+        self.buffer.clear();
+        encode_payload(payload.view(), &mut self.buffer);
+        if self.recorder.is_some() {
+            record(
+                &self.recorder,
+                Direction::ToServer,
+                &String::from_utf8_lossy(&self.buffer),
+            );
+        }
+        self.buffer.put_u8(0u8);
         self.underlying
-            .write_all_buf(&mut deq)
+            .write_all(&self.buffer)
             .await
-            .with_context(|| format!("Failed to send payload to socket"))?;
+            .context("Failed to send payload to socket")?;
         self.underlying
             .flush()
             .await
-            .with_context(|| format!("Failed to send (flush) payload to socket"))?;
+            .context("Failed to send (flush) payload to socket")?;
+        if let Some(history) = &self.history {
+            let frame = &self.buffer[..self.buffer.len() - 1];
+            history.append(
+                Direction::ToServer,
+                &String::from_utf8_lossy(frame),
+                &payload,
+            );
+        }
 
         Ok(())
     }
@@ -70,12 +158,12 @@ impl DspClient {
 
         // Split connection into RW
         let (reader_raw, writer_raw) = stream.into_split();
-        let reader = DspReader {
-            underlying: BufReader::new(reader_raw),
-        };
-        let mut writer = DspWriter {
-            underlying: writer_raw,
-        };
+        let reader = DspReader::new(reader_raw)
+            .recording(config.recorder.clone())
+            .history(config.history.clone());
+        let mut writer = DspWriter::new(writer_raw)
+            .recording(config.recorder.clone())
+            .history(config.history.clone());
 
         // Join the server
         writer
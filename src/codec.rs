@@ -1,215 +1,286 @@
 use crate::{logger::NS_CONN, protocol::*};
 use anyhow::{Context, Result, anyhow};
+use bytes::{BufMut, BytesMut};
 use log::warn;
 use nom::{
     IResult, Parser,
     bytes::complete::{tag, take_while_m_n},
     combinator::{flat_map, map, map_res, opt},
 };
-use std::{mem, str::FromStr};
+use std::{fmt::Write, str, str::FromStr};
 use tokio::io::AsyncBufReadExt;
 
+/// Longest username in characters
+pub const MAX_USERNAME_LENGTH: usize = 32;
+/// Longest message type in characters
+pub const MAX_TYPE_LENGTH: usize = 20;
+/// Longest content of a frame in characters
+pub const MAX_CONTENT_LENGTH: usize = 1024;
+/// Longest frame read by [`read_text`], longer ones can't be valid
+pub const MAX_FRAME_LENGTH: usize = max_frame_length(MAX_CONTENT_LENGTH);
+
+/// Longest frame in bytes whose content has up to `content` characters, all of them 4 bytes of
+/// UTF-8, with the two spaces after the username and the message type
+pub const fn max_frame_length(content: usize) -> usize {
+    4 * (MAX_USERNAME_LENGTH + MAX_TYPE_LENGTH + content) + 2
+}
+
 fn username(input: &str) -> IResult<&str, &str> {
-    take_while_m_n(1, 32, |c: char| c.is_alphanumeric() || c == '_').parse(input)
+    take_while_m_n(1, MAX_USERNAME_LENGTH, |c: char| {
+        c.is_alphanumeric() || c == '_'
+    })
+    .parse(input)
 }
 
 fn message_type(input: &str) -> IResult<&str, MessageType> {
     map_res(
-        take_while_m_n(1, 20, |c: char| c.is_alphabetic() && c.is_uppercase()),
+        take_while_m_n(1, MAX_TYPE_LENGTH, |c: char| {
+            c.is_alphabetic() && c.is_uppercase()
+        }),
         MessageType::from_str,
     )
     .parse(input)
 }
 
-fn join_message(input: &str) -> IResult<&str, JoinMessage> {
-    Ok((input, JoinMessage {}))
-}
-
-fn dsp_join_message(input: &str) -> IResult<&str, DspMessage> {
-    map(join_message, DspMessage::JoinMessage).parse(input)
+fn join_message(input: &str) -> IResult<&str, DspMessageRef<'_>> {
+    Ok((input, DspMessageRef::JoinMessage))
 }
 
-fn quit_message(input: &str) -> IResult<&str, QuitMessage> {
-    Ok((input, QuitMessage {}))
+fn quit_message(input: &str) -> IResult<&str, DspMessageRef<'_>> {
+    Ok((input, DspMessageRef::QuitMessage))
 }
 
-fn dsp_quit_message(input: &str) -> IResult<&str, DspMessage> {
-    map(quit_message, DspMessage::QuitMessage).parse(input)
+fn message_message(input: &str) -> IResult<&str, DspMessageRef<'_>> {
+    Ok(("", DspMessageRef::MessageMessage { text: input }))
 }
 
-fn message_message(input: &str) -> IResult<&str, MessageMessage> {
-    Ok(("", MessageMessage {
-        text: input.to_string(),
-    }))
-}
-
-fn dsp_message_message(input: &str) -> IResult<&str, DspMessage> {
-    map(message_message, DspMessage::MessageMessage).parse(input)
-}
-
-fn challenge_message(input: &str) -> IResult<&str, ChallengeMessage> {
+fn challenge_message(input: &str) -> IResult<&str, DspMessageRef<'_>> {
     map(
         (
-            take_while_m_n(1, 99, |c: char| c.is_numeric()),
+            map_res(
+                take_while_m_n(1, 2, |c: char| c.is_ascii_digit()),
+                u64::from_str,
+            ),
             tag(" "),
             take_while_m_n(0, 64, |c: char| c.is_alphanumeric()),
         ),
-        |(n, _, phrase): (&str, &str, &str)| ChallengeMessage {
-            n: n.len() as u64,
-            phrase: phrase.to_string(),
-        },
+        |(n, _, phrase)| DspMessageRef::ChallengeMessage { n, phrase },
     )
     .parse(input)
 }
 
-fn dsp_challange_message(input: &str) -> IResult<&str, DspMessage> {
-    map(challenge_message, DspMessage::ChallengeMessage).parse(input)
+fn rescinded_message(input: &str) -> IResult<&str, DspMessageRef<'_>> {
+    Ok((input, DspMessageRef::RescindedMessage))
 }
 
-fn rescinded_message(input: &str) -> IResult<&str, RescindedMessage> {
-    Ok((input, RescindedMessage {}))
+fn response_message(input: &str) -> IResult<&str, DspMessageRef<'_>> {
+    Ok(("", DspMessageRef::ResponseMessage { phrase: input }))
 }
 
-fn dsp_rescinded_message(input: &str) -> IResult<&str, DspMessage> {
-    map(rescinded_message, DspMessage::RescindedMessage).parse(input)
-}
-
-fn response_message(input: &str) -> IResult<&str, ResponseMessage> {
-    Ok(("", ResponseMessage {
-        phrase: input.to_string(),
-    }))
-}
-
-fn dsp_response_message(input: &str) -> IResult<&str, DspMessage> {
-    map(response_message, DspMessage::ResponseMessage).parse(input)
-}
-
-fn error_message(input: &str) -> IResult<&str, ErrorMessage> {
-    Ok(("", ErrorMessage {
-        text: input.to_string(),
-    }))
-}
-
-fn dsp_error_message(input: &str) -> IResult<&str, DspMessage> {
-    map(error_message, DspMessage::ErrorMessage).parse(input)
+fn error_message(input: &str) -> IResult<&str, DspMessageRef<'_>> {
+    Ok(("", DspMessageRef::ErrorMessage { text: input }))
 }
 
 fn message_of_type<'a>(
     message_type: MessageType,
-) -> impl Parser<&'a str, Output = DspMessage, Error = nom::error::Error<&'a str>> {
+) -> impl Parser<&'a str, Output = DspMessageRef<'a>, Error = nom::error::Error<&'a str>> {
     match message_type {
-        MessageType::JOIN => dsp_join_message,
-        MessageType::QUIT => dsp_quit_message,
-        MessageType::MESSAGE => dsp_message_message,
-        MessageType::CHALLENGE => dsp_challange_message,
-        MessageType::RESCINDED => dsp_rescinded_message,
-        MessageType::RESPONSE => dsp_response_message,
-        MessageType::ERROR => dsp_error_message,
+        MessageType::JOIN => join_message,
+        MessageType::QUIT => quit_message,
+        MessageType::MESSAGE => message_message,
+        MessageType::CHALLENGE => challenge_message,
+        MessageType::RESCINDED => rescinded_message,
+        MessageType::RESPONSE => response_message,
+        MessageType::ERROR => error_message,
     }
 }
 
-fn message_with_type(input: &str) -> IResult<&str, DspMessage> {
+fn message_with_type(input: &str) -> IResult<&str, DspMessageRef<'_>> {
     flat_map((message_type, opt(tag(" "))), |(mtype, _)| {
         message_of_type(mtype)
     })
     .parse(input)
 }
 
-fn payload(input: &str) -> IResult<&str, DspPayload> {
+fn payload(input: &str) -> IResult<&str, DspPayloadRef<'_>> {
     map(
         (username, tag(" "), message_with_type),
-        |(username, _, message)| DspPayload {
-            username: username.to_string(),
-            message,
-        },
+        |(username, _, message)| DspPayloadRef { username, message },
     )
     .parse(input)
 }
 
-fn parse_payload(input: String) -> Result<DspPayload> {
-    payload(&input)
+/// Parses a frame without its terminator, the payload borrows from it
+pub fn parse_frame(frame: &[u8]) -> Result<DspPayloadRef<'_>> {
+    let text = str::from_utf8(frame).context("Frame is not valid UTF-8")?;
+    parse_text(text)
+}
+
+pub fn parse_text(input: &str) -> Result<DspPayloadRef<'_>> {
+    payload(input)
         .map(|(_, payload)| payload)
         .map_err(|e| anyhow!(e.to_string()))
-        .with_context(|| format!("Failed to parse DSP payload"))
+        .context("Failed to parse DSP payload")
 }
 
-fn stringify_payload(input: DspPayload) -> String {
-    let username = input.username;
-    let message = match input.message {
-        DspMessage::JoinMessage(_) => format!("JOIN"),
-        DspMessage::QuitMessage(_) => format!("QUIT"),
-        DspMessage::MessageMessage(m) => format!("MESSAGE {}", m.text),
-        DspMessage::ChallengeMessage(m) => format!(
-            "CHALLENGE {} {}",
-            Vec::<char>::with_capacity(m.n as usize)
-                .into_iter()
-                .map(|_| '0')
-                .collect::<String>(),
-            m.phrase
-        ),
-        DspMessage::RescindedMessage(_) => format!("RESCINDED"),
-        DspMessage::ResponseMessage(m) => format!("RESPONSE {}", m.phrase),
-        DspMessage::ErrorMessage(m) => format!("ERROR {}", m.text),
-    };
-    format!("{} {}", username, message)
+pub fn parse_payload(input: String) -> Result<DspPayload> {
+    parse_text(&input).map(|payload| payload.to_payload())
+}
+
+/// Appends a payload without its terminator to the buffer, which can be reused across frames
+pub fn encode_payload(payload: DspPayloadRef, buffer: &mut BytesMut) {
+    buffer.put_slice(payload.username.as_bytes());
+    match payload.message {
+        DspMessageRef::JoinMessage => buffer.put_slice(b" JOIN"),
+        DspMessageRef::QuitMessage => buffer.put_slice(b" QUIT"),
+        DspMessageRef::MessageMessage { text } => {
+            buffer.put_slice(b" MESSAGE ");
+            buffer.put_slice(text.as_bytes());
+        }
+        DspMessageRef::ChallengeMessage { n, phrase } => {
+            // Formatting into a BytesMut grows it, so it can't fail
+            let _ = write!(buffer, " CHALLENGE {} ", n);
+            buffer.put_slice(phrase.as_bytes());
+        }
+        DspMessageRef::RescindedMessage => buffer.put_slice(b" RESCINDED"),
+        DspMessageRef::ResponseMessage { phrase } => {
+            buffer.put_slice(b" RESPONSE ");
+            buffer.put_slice(phrase.as_bytes());
+        }
+        DspMessageRef::ErrorMessage { text } => {
+            buffer.put_slice(b" ERROR ");
+            buffer.put_slice(text.as_bytes());
+        }
+    }
 }
 
 pub fn payload_bytes(input: DspPayload) -> Vec<u8> {
-    stringify_payload(input).into_bytes()
+    let mut buffer = BytesMut::new();
+    encode_payload(input.view(), &mut buffer);
+    buffer.into()
 }
 
-pub async fn read_buffer_until_payload<R: AsyncBufReadExt + Unpin>(
+/// Reads the next \0 terminated frame into a buffer which is cleared first, without the terminator
+pub async fn read_frame<R: AsyncBufReadExt + Unpin>(
     buf_reader: &mut R,
-) -> Result<DspPayload> {
-    loop {
-        // Define reader buffers
-        let mut byte_payload = vec![];
+    frame: &mut Vec<u8>,
+) -> Result<()> {
+    read_frame_limited(buf_reader, frame, usize::MAX).await?;
+    Ok(())
+}
 
-        // Read until \0
-        let payload_read = buf_reader.read_until(b'\0', &mut byte_payload);
-        let read_count = payload_read
+/// Like [`read_frame`], but a frame longer than `limit` bytes is skipped up to its terminator
+/// without buffering it, returns whether the frame fit
+pub async fn read_frame_limited<R: AsyncBufReadExt + Unpin>(
+    buf_reader: &mut R,
+    frame: &mut Vec<u8>,
+    limit: usize,
+) -> Result<bool> {
+    frame.clear();
+    let mut read_count = 0;
+    let mut fits = true;
+    loop {
+        let available = buf_reader
+            .fill_buf()
             .await
-            .with_context(|| format!("Failed while reading next message bytes"))?;
+            .context("Failed while reading next message bytes")?;
+
+        // Check if connection closed, a frame cut short by it is still returned
+        if available.is_empty() {
+            if read_count == 0 {
+                return Err(anyhow!(
+                    "Reached EOF while reading next message bytes, assuming connection closed"
+                ));
+            }
+            return Ok(fits);
+        }
 
-        // Check if connection closed
-        if read_count == 0 {
-            return Err(anyhow!(
-                "Reached EOF while reading next message bytes, assuming connection closed"
-            ));
+        // Read until \0, which is dropped
+        let (chunk, used, terminated) = match available.iter().position(|&byte| byte == 0) {
+            Some(end) => (&available[..end], end + 1, true),
+            None => (available, available.len(), false),
+        };
+        if fits && frame.len() + chunk.len() > limit {
+            fits = false;
+            frame.clear();
         }
+        if fits {
+            frame.extend_from_slice(chunk);
+        }
+        buf_reader.consume(used);
+        read_count += used;
+        if terminated {
+            return Ok(fits);
+        }
+    }
+}
 
-        // Drop null-terminator if present
-        if byte_payload.last() == Some(&0u8) {
-            byte_payload.remove(byte_payload.len() - 1);
+/// Reads the next \0 terminated frame into a buffer as text, frames which aren't UTF-8 or are
+/// longer than [`MAX_FRAME_LENGTH`] are skipped
+pub async fn read_text<'a, R: AsyncBufReadExt + Unpin>(
+    buf_reader: &mut R,
+    frame: &'a mut Vec<u8>,
+) -> Result<&'a str> {
+    loop {
+        if !read_frame_limited(buf_reader, frame, MAX_FRAME_LENGTH).await? {
+            warn!(
+                target: NS_CONN,
+                "Received message is longer than {} bytes, ignoring",
+                MAX_FRAME_LENGTH
+            );
+            continue;
         }
 
         // Parse bytes into UTF-8
-        let text_payload = match String::from_utf8(mem::take(&mut byte_payload))
-            .with_context(|| format!("Received message is not a valid UTF-8 byte stream, ignoring"))
+        match str::from_utf8(frame)
+            .context("Received message is not a valid UTF-8 byte stream, ignoring")
         {
-            Err(err) => {
-                warn!(target: NS_CONN, "{}", err);
-                continue;
-            }
-            Ok(text_payload) => text_payload,
-        };
+            Err(err) => warn!(target: NS_CONN, "{:#}", err),
+            Ok(_) => break,
+        }
+    }
+    // Converted again, returning the text from inside the loop doesn't pass the borrow checker
+    Ok(str::from_utf8(frame)?)
+}
 
-        // Parse text into message payload
-        let message_payload = parse_payload(text_payload).with_context(|| {
-            format!("Failed to parse UTF-8 byte message as a DSP message payload")
-        })?;
+/// Reads the next payload borrowing from the buffer, as [`read_text`] followed by [`parse_text`]
+pub async fn read_payload<'a, R: AsyncBufReadExt + Unpin>(
+    buf_reader: &mut R,
+    frame: &'a mut Vec<u8>,
+) -> Result<DspPayloadRef<'a>> {
+    let text = read_text(buf_reader, frame).await?;
+    parse_text(text).context("Failed to parse UTF-8 byte message as a DSP message payload")
+}
 
-        // Return valid message payload
-        return Ok(message_payload);
-    }
+/// Reads the next frame as owned text, see [`read_text`]
+pub async fn read_buffer_until_frame<R: AsyncBufReadExt + Unpin>(
+    buf_reader: &mut R,
+) -> Result<String> {
+    let mut frame = vec![];
+    read_text(buf_reader, &mut frame).await.map(str::to_string)
+}
+
+/// Reads the next owned payload, see [`read_payload`]
+pub async fn read_buffer_until_payload<R: AsyncBufReadExt + Unpin>(
+    buf_reader: &mut R,
+) -> Result<DspPayload> {
+    let mut frame = vec![];
+    read_payload(buf_reader, &mut frame)
+        .await
+        .map(|payload| payload.to_payload())
 }
 
 #[cfg(test)]
 mod tests {
     use nom::AsBytes;
+    use proptest::prelude::*;
 
     use super::*;
 
+    fn stringify_payload(input: DspPayload) -> String {
+        String::from_utf8(payload_bytes(input)).unwrap()
+    }
+
     fn serde_check(text: &str, data: DspPayload) {
         let de = parse_payload(text.to_string()).map_err(|e| e.to_string());
         assert_eq!(de, Ok(data));
@@ -232,11 +303,34 @@ mod tests {
                 }),
             },
         );
+        serde_check("server CHALLENGE 12 abcXYZ123", DspPayload {
+            username: String::from("server"),
+            message: DspMessage::ChallengeMessage(ChallengeMessage {
+                n: 12,
+                phrase: String::from("abcXYZ123"),
+            }),
+        });
+    }
+
+    #[test]
+    fn check_borrowed_frame() {
+        let frame = b"server CHALLENGE 3 abc";
+        let payload = parse_frame(frame).unwrap();
+        assert_eq!(payload, DspPayloadRef {
+            username: "server",
+            message: DspMessageRef::ChallengeMessage { n: 3, phrase: "abc" },
+        });
+        assert!(parse_frame(b"bob MESSAGE \xff").is_err());
+
+        let mut buffer = BytesMut::from(&b"stale"[..]);
+        buffer.clear();
+        encode_payload(payload, &mut buffer);
+        assert_eq!(&buffer[..], frame);
     }
 
     #[tokio::test]
     async fn check_payload_buffer_read() {
-        let text = format!("testuser MESSAGE test");
+        let text = "testuser MESSAGE test".to_string();
         let text_buf = text.as_bytes();
         let terminator_buf = &[0u8];
         let concat_buf = [text_buf, terminator_buf].concat();
@@ -254,4 +348,88 @@ mod tests {
             })
         );
     }
+
+    #[tokio::test]
+    async fn check_limited_frame_read() {
+        let bytes = [&[b'x'; 5000][..], b"\0bob JOIN\0bob QUIT"].concat();
+        let mut reader = tokio::io::BufReader::with_capacity(64, bytes.as_bytes());
+        let mut frame = vec![];
+        let fits = read_frame_limited(&mut reader, &mut frame, 1024).await;
+        assert!(!fits.unwrap());
+        assert!(frame.capacity() <= 2048);
+
+        let fits = read_frame_limited(&mut reader, &mut frame, 1024).await;
+        assert!(fits.unwrap());
+        assert_eq!(frame, b"bob JOIN");
+        // The last frame is cut short by EOF
+        let fits = read_frame_limited(&mut reader, &mut frame, 1024).await;
+        assert!(fits.unwrap());
+        assert_eq!(frame, b"bob QUIT");
+        let closed = read_frame_limited(&mut reader, &mut frame, 1024).await;
+        assert!(closed.is_err());
+    }
+
+    #[tokio::test]
+    async fn check_borrowed_payload_read() {
+        let oversized = [b'x'; MAX_FRAME_LENGTH + 1];
+        let bytes = [&b"\xff\xfe\0"[..], &oversized, b"\0bob MESSAGE hi\0"].concat();
+        let mut stream = bytes.as_bytes();
+        let mut frame = vec![];
+        let payload = read_payload(&mut stream, &mut frame).await.unwrap();
+        assert_eq!(payload.username, "bob");
+        assert_eq!(
+            payload.message,
+            DspMessageRef::MessageMessage { text: "hi" }
+        );
+    }
+
+    fn dsp_message() -> impl Strategy<Value = DspMessage> {
+        let text = "[^\\x00]{0,64}";
+        let challenge = (1..100u64, "[A-Za-z0-9]{0,64}")
+            .prop_map(|(n, phrase)| ChallengeMessage { n, phrase });
+        prop_oneof![
+            Just(DspMessage::JoinMessage(JoinMessage {})),
+            Just(DspMessage::QuitMessage(QuitMessage {})),
+            text.prop_map(|text| DspMessage::MessageMessage(MessageMessage { text })),
+            challenge.prop_map(DspMessage::ChallengeMessage),
+            Just(DspMessage::RescindedMessage(RescindedMessage {})),
+            text.prop_map(|phrase| DspMessage::ResponseMessage(ResponseMessage { phrase })),
+            text.prop_map(|text| DspMessage::ErrorMessage(ErrorMessage { text })),
+        ]
+    }
+
+    /// Text which often parses, any message type followed by arbitrary content
+    fn frame_text() -> impl Strategy<Value = String> {
+        let types = vec![
+            "JOIN",
+            "QUIT",
+            "MESSAGE",
+            "CHALLENGE",
+            "RESCINDED",
+            "RESPONSE",
+            "ERROR",
+        ];
+        ("\\w{1,33}", prop::sample::select(types), "\\PC{0,30}").prop_map(
+            |(username, message_type, rest)| format!("{} {}{}", username, message_type, rest),
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn check_payload_round_trip(username in "[A-Za-z0-9_]{1,32}", message in dsp_message()) {
+            let payload = DspPayload { username, message };
+            let parsed = parse_payload(stringify_payload(payload.clone()))
+                .map_err(|e| e.to_string());
+            prop_assert_eq!(parsed, Ok(payload));
+        }
+
+        #[test]
+        fn check_parse_is_stable(text in frame_text()) {
+            if let Ok(payload) = parse_payload(text) {
+                let reparsed = parse_payload(stringify_payload(payload.clone()))
+                    .map_err(|e| e.to_string());
+                prop_assert_eq!(reparsed, Ok(payload));
+            }
+        }
+    }
 }
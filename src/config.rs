@@ -1,31 +1,303 @@
-use crate::args::Args;
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use toml_edit::{Array, DocumentMut, table, value};
+
+use crate::{
+    app::{
+        emoji::EmojiConfig,
+        filter::{ChatFilter, FilterConfig},
+        keymap::{Action, Keymap, KeymapPreset},
+        layout::LayoutConfig,
+        links::LinksConfig,
+        notify::NotifyConfig,
+        theme::{Theme, ThemeConfig},
+    },
+    args::Args,
+    capture::Recorder,
+    history::{History, HistoryConfig},
+};
 
 pub struct DspLogConfig {
     pub log_file: Option<String>,
 }
 
+#[derive(Clone)]
 pub struct DspClientConfig {
     pub server_address: String,
     pub username: String,
+    /// Capture file of every frame sent and received, kept across reconnects
+    pub recorder: Option<Recorder>,
+    /// Store of earlier sessions with the server, every payload of this one is added to it
+    pub history: Option<History>,
+}
+
+/// Session played back from a capture file instead of connecting
+pub struct DspReplayConfig {
+    pub path: PathBuf,
+    /// How many times faster than recorded
+    pub speed: f64,
+}
+
+pub struct DspUiConfig {
+    pub highlight_words: Vec<String>,
+    pub keymap: Keymap,
+    pub theme: Theme,
+    pub mouse_capture: bool,
+    pub filter: ChatFilter,
+    pub notify: NotifyConfig,
+    pub links: LinksConfig,
+    pub emoji: EmojiConfig,
+    pub layout: LayoutConfig,
+    /// Where filter and layout changes are saved, the explicit or the default config path
+    pub config_path: Option<PathBuf>,
 }
 
 pub struct Config {
     pub client: DspClientConfig,
+    pub replay: Option<DspReplayConfig>,
     pub log: DspLogConfig,
+    pub ui: DspUiConfig,
+}
+
+/// Contents of the TOML config file, everything is optional
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileConfig {
+    pub highlight_words: Vec<String>,
+    pub keymap: FileKeymapConfig,
+    pub theme: ThemeConfig,
+    /// Scroll and click with the mouse, defaults to true
+    pub mouse: Option<bool>,
+    pub filters: FilterConfig,
+    pub notify: NotifyConfig,
+    pub links: LinksConfig,
+    pub emoji: EmojiConfig,
+    pub layout: LayoutConfig,
+    pub history: HistoryConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileKeymapConfig {
+    pub preset: KeymapPreset,
+    pub bindings: BTreeMap<Action, Vec<String>>,
+}
+
+impl FileConfig {
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("uiop-chat").join("config.toml"))
+    }
+
+    pub fn read(path: &Path) -> Result<FileConfig> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file '{}'", path.display()))?;
+        toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse config file '{}'", path.display()))
+    }
+
+    /// Saves the `[filters]` table, creating the file if needed and keeping the rest of it intact
+    pub fn write_filters(path: &Path, filters: &FilterConfig) -> Result<()> {
+        FileConfig::rewrite(path, |contents| update_filters(contents, filters))
+    }
+
+    /// Saves the `[layout]` table like [`FileConfig::write_filters`]
+    pub fn write_layout(path: &Path, layout: &LayoutConfig) -> Result<()> {
+        FileConfig::rewrite(path, |contents| update_layout(contents, layout))
+    }
+
+    fn rewrite(path: &Path, update: impl FnOnce(&str) -> Result<String>) -> Result<()> {
+        let contents = match path.exists() {
+            true => fs::read_to_string(path)
+                .with_context(|| format!("Failed to read config file '{}'", path.display()))?,
+            false => String::new(),
+        };
+        let contents = update(&contents)
+            .with_context(|| format!("Failed to parse config file '{}'", path.display()))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).with_context(|| {
+                format!("Failed to create config directory '{}'", dir.display())
+            })?;
+        }
+        fs::write(path, contents)
+            .with_context(|| format!("Failed to write config file '{}'", path.display()))
+    }
+}
+
+fn update_filters(contents: &str, filters: &FilterConfig) -> Result<String> {
+    let mut document: DocumentMut = contents.parse()?;
+    if !document.contains_table("filters") {
+        document["filters"] = table();
+    }
+    let section = &mut document["filters"];
+    section["ignore"] = value(Array::from_iter(&filters.ignore));
+    section["patterns"] = value(Array::from_iter(&filters.patterns));
+    section["hide_joins"] = value(filters.hide_joins);
+    section["collapse_repeats"] = value(filters.collapse_repeats);
+    Ok(document.to_string())
+}
+
+fn update_layout(contents: &str, layout: &LayoutConfig) -> Result<String> {
+    let mut document: DocumentMut = contents.parse()?;
+    if !document.contains_table("layout") {
+        document["layout"] = table();
+    }
+    let section = &mut document["layout"];
+    section["tabs"] = value(layout.tabs);
+    section["help"] = value(layout.help);
+    section["roster"] = value(layout.roster);
+    section["logs"] = value(layout.logs);
+    section["roster_width"] = value(layout.roster_width as i64);
+    section["logs_height"] = value(layout.logs_height as i64);
+    section["compact"] = value(layout.compact);
+    Ok(document.to_string())
 }
 
 impl Config {
-    pub fn from_args(args: Args) -> Config {
+    /// Combines CLI args with the config file, which may be absent at its default path
+    pub fn load(args: Args) -> Result<Config> {
+        let path = match args.config.as_deref() {
+            Some(path) => Some(PathBuf::from(path)),
+            None => FileConfig::default_path(),
+        };
+        let file = match &path {
+            Some(path) if args.config.is_some() || path.exists() => FileConfig::read(path)?,
+            _ => FileConfig::default(),
+        };
+        let mut config = Config::from_parts(args, file)?;
+        config.ui.config_path = path;
+        Ok(config)
+    }
+
+    pub fn from_parts(args: Args, file: FileConfig) -> Result<Config> {
         let server_address = args.server_address;
         let username = args.username;
+        let recorder = match &args.record {
+            Some(path) => Some(Recorder::create(Path::new(path))?),
+            None => None,
+        };
+        // A replayed session is neither stored nor preceded by earlier ones
+        let history = match file.history.server_dir(&server_address) {
+            Some(dir) if file.history.enabled && !args.no_history && args.replay.is_none() => Some(
+                History::open(&dir, &file.history)
+                    .context("Failed to open chat history, --no-history skips it")?,
+            ),
+            _ => None,
+        };
         let client = DspClientConfig {
             server_address,
             username,
+            recorder,
+            history,
         };
+        let replay = args.replay.map(|path| DspReplayConfig {
+            path: PathBuf::from(path),
+            speed: args.replay_speed,
+        });
 
         let log_file = args.log_file;
         let log = DspLogConfig { log_file };
 
-        Config { client, log }
+        let mut highlight_words = file.highlight_words;
+        highlight_words.extend(args.highlight_words);
+        let keymap = Keymap::new(file.keymap.preset, &file.keymap.bindings)
+            .context("Invalid keymap in config file")?;
+        let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        let theme = Theme::new(&file.theme, no_color).context("Invalid theme in config file")?;
+        let mouse_capture = file.mouse.unwrap_or(true) && !args.no_mouse;
+        let filter = ChatFilter::new(file.filters).context("Invalid filters in config file")?;
+        let mut links = file.links;
+        // The Linux console and dumb terminals print OSC 8 sequences as garbage
+        if env::var("TERM").is_ok_and(|term| term == "linux" || term == "dumb") {
+            links.hyperlinks = false;
+        }
+        let ui = DspUiConfig {
+            highlight_words,
+            keymap,
+            theme,
+            mouse_capture,
+            filter,
+            notify: file.notify,
+            links,
+            emoji: file.emoji,
+            layout: file.layout,
+            config_path: None,
+        };
+
+        Ok(Config {
+            client,
+            replay,
+            log,
+            ui,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn check_file_config_parsing() {
+        let file: FileConfig = toml::from_str(
+            r#"
+            highlight_words = ["rust"]
+
+            [keymap]
+            preset = "vi"
+
+            [keymap.bindings]
+            next_tab = ["ctrl-n"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(file.highlight_words, vec![String::from("rust")]);
+        assert_eq!(file.keymap.preset, KeymapPreset::Vi);
+        assert_eq!(
+            file.keymap.bindings.get(&Action::NextTab),
+            Some(&vec![String::from("ctrl-n")])
+        );
+    }
+
+    #[test]
+    fn check_filters_update() {
+        let filters = FilterConfig {
+            ignore: BTreeSet::from([String::from("spammer")]),
+            hide_joins: true,
+            ..FilterConfig::default()
+        };
+        let contents = "# My settings\nmouse = false\n\n[filters]\npatterns = [\"old\"]\n";
+        let contents = update_filters(contents, &filters).unwrap();
+        assert!(contents.starts_with("# My settings\nmouse = false\n"));
+
+        let file: FileConfig = toml::from_str(&contents).unwrap();
+        assert_eq!(file.mouse, Some(false));
+        assert_eq!(file.filters.ignore, filters.ignore);
+        assert!(file.filters.patterns.is_empty());
+        assert!(file.filters.hide_joins);
+    }
+
+    #[test]
+    fn check_layout_update() {
+        let layout = LayoutConfig {
+            roster: true,
+            roster_width: 30,
+            ..LayoutConfig::default()
+        };
+        let contents = update_layout(
+            "[layout]
+logs = true
+",
+            &layout,
+        )
+        .unwrap();
+        let file: FileConfig = toml::from_str(&contents).unwrap();
+        assert_eq!(file.layout, layout);
     }
 }
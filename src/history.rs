@@ -0,0 +1,311 @@
+use crate::{
+    capture::{CaptureRecord, Direction},
+    logger::NS_CONN,
+    protocol::{DspMessage, DspPayload},
+};
+
+use anyhow::{Context, Result};
+use log::warn;
+use serde::Deserialize;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+/// A new segment is started once the current one grows past this many bytes
+const SEGMENT_SIZE: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    pub enabled: bool,
+    /// Defaults to `uiop-chat/history` in the user data dir, every server gets a directory in it
+    pub dir: Option<PathBuf>,
+    /// Messages of earlier sessions shown on startup
+    pub load: usize,
+    /// Segments last written longer ago are deleted, 0 keeps them forever
+    pub max_age_days: u64,
+    /// Oldest segments are deleted while a server's history is larger, 0 for no limit
+    pub max_size_mb: u64,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        HistoryConfig {
+            enabled: true,
+            dir: None,
+            load: 100,
+            max_age_days: 30,
+            max_size_mb: 50,
+        }
+    }
+}
+
+impl HistoryConfig {
+    /// Directory of a server's history, its address with anything unusual in file names replaced
+    pub fn server_dir(&self, server_address: &str) -> Option<PathBuf> {
+        let root = match &self.dir {
+            Some(dir) => dir.clone(),
+            None => dirs::data_dir()?.join("uiop-chat").join("history"),
+        };
+        let name: String = server_address
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        Some(root.join(name))
+    }
+}
+
+/// Append-only log of every payload sent to and received from a server,
+/// split into numbered JSONL segments in the capture format
+pub struct HistoryStore {
+    dir: PathBuf,
+    config: HistoryConfig,
+    segment: BufWriter<File>,
+    segment_number: u64,
+    segment_size: u64,
+}
+
+impl HistoryStore {
+    /// Opens a server's history, deleting segments beyond the retention limits
+    pub fn open(dir: &Path, config: &HistoryConfig) -> Result<HistoryStore> {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create history directory '{}'", dir.display()))?;
+        let (segment_number, segment_size) = match segments(dir)?.last() {
+            Some((number, path)) => match path.metadata().map(|m| m.len()) {
+                Ok(size) if size < SEGMENT_SIZE => (*number, size),
+                _ => (number + 1, 0),
+            },
+            None => (1, 0),
+        };
+        let store = HistoryStore {
+            dir: dir.to_path_buf(),
+            config: config.clone(),
+            segment: open_segment(&segment_path(dir, segment_number))?,
+            segment_number,
+            segment_size,
+        };
+        store.enforce_retention()?;
+        Ok(store)
+    }
+
+    pub fn append(&mut self, record: &CaptureRecord) -> Result<()> {
+        let mut line = serde_json::to_vec(record).context("Failed to encode history record")?;
+        line.push(b'\n');
+        if self.segment_size > 0 && self.segment_size + line.len() as u64 > SEGMENT_SIZE {
+            self.segment_number += 1;
+            self.segment = open_segment(&segment_path(&self.dir, self.segment_number))?;
+            self.segment_size = 0;
+            self.enforce_retention()?;
+        }
+        self.segment
+            .write_all(&line)
+            .and_then(|_| self.segment.flush())
+            .context("Failed to write history record")?;
+        self.segment_size += line.len() as u64;
+        Ok(())
+    }
+
+    /// The newest received messages up to the configured amount, oldest first
+    pub fn recent_messages(&self) -> Result<Vec<CaptureRecord>> {
+        let mut messages = vec![];
+        for (_, path) in segments(&self.dir)?.iter().rev() {
+            if messages.len() >= self.config.load {
+                break;
+            }
+            let text = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read history segment '{}'", path.display()))?;
+            // Lines cut short by a crash are skipped
+            let records = text
+                .lines()
+                .filter_map(|line| serde_json::from_str::<CaptureRecord>(line).ok())
+                .filter(|record| {
+                    record.direction == Direction::ToClient
+                        && matches!(
+                            record.payload,
+                            Some(DspPayload {
+                                message: DspMessage::MessageMessage(_),
+                                ..
+                            })
+                        )
+                });
+            let mut segment_messages: Vec<_> = records.collect();
+            let missing = self.config.load - messages.len();
+            let start = segment_messages.len().saturating_sub(missing);
+            segment_messages.drain(..start);
+            segment_messages.append(&mut messages);
+            messages = segment_messages;
+        }
+        Ok(messages)
+    }
+
+    /// Deletes segments older than the maximum age, then the oldest ones until the rest fit,
+    /// the segment being written is always kept
+    fn enforce_retention(&self) -> Result<()> {
+        let max_age = Duration::from_secs(self.config.max_age_days.saturating_mul(24 * 60 * 60));
+        let max_size = self.config.max_size_mb.saturating_mul(1024 * 1024);
+        let segments: Vec<_> = segments(&self.dir)?
+            .into_iter()
+            .filter(|(number, _)| *number != self.segment_number)
+            .filter_map(|(_, path)| Some((path.metadata().ok()?, path)))
+            .collect();
+        let mut total = self.segment_size + segments.iter().map(|(m, _)| m.len()).sum::<u64>();
+        let now = SystemTime::now();
+        for (metadata, path) in segments {
+            let age = metadata
+                .modified()
+                .ok()
+                .and_then(|time| now.duration_since(time).ok());
+            let expired = self.config.max_age_days > 0 && age.is_some_and(|age| age > max_age);
+            let oversized = self.config.max_size_mb > 0 && total > max_size;
+            if !expired && !oversized {
+                continue;
+            }
+            match fs::remove_file(&path) {
+                Ok(()) => total -= metadata.len(),
+                Err(err) => warn!(
+                    target: NS_CONN,
+                    "Failed to delete history segment '{}': {}",
+                    path.display(),
+                    err
+                ),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Numbered segments of a history directory, oldest first
+fn segments(dir: &Path) -> Result<Vec<(u64, PathBuf)>> {
+    let entries = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read history directory '{}'", dir.display()))?;
+    let mut segments: Vec<_> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let number = path.file_stem()?.to_str()?.parse().ok()?;
+            (path.extension()? == "jsonl").then_some((number, path))
+        })
+        .collect();
+    segments.sort();
+    Ok(segments)
+}
+
+fn segment_path(dir: &Path, number: u64) -> PathBuf {
+    dir.join(format!("{:08}.jsonl", number))
+}
+
+fn open_segment(path: &Path) -> Result<BufWriter<File>> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open history segment '{}'", path.display()))?;
+    Ok(BufWriter::new(file))
+}
+
+/// History store shared by the reader and writer of every connection to a server
+#[derive(Clone)]
+pub struct History {
+    store: Arc<Mutex<HistoryStore>>,
+}
+
+impl History {
+    pub fn open(dir: &Path, config: &HistoryConfig) -> Result<History> {
+        Ok(History {
+            store: Arc::new(Mutex::new(HistoryStore::open(dir, config)?)),
+        })
+    }
+
+    /// Failing to store a payload is only logged, it never breaks the session
+    pub fn append(&self, direction: Direction, frame: &str, payload: &DspPayload) {
+        let record = CaptureRecord::parsed(direction, frame.to_string(), Some(payload.clone()));
+        if let Err(err) = self.store.lock().unwrap().append(&record) {
+            warn!(target: NS_CONN, "{:#}", err);
+        }
+    }
+
+    pub fn recent_messages(&self) -> Result<Vec<CaptureRecord>> {
+        self.store.lock().unwrap().recent_messages()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::parse_payload;
+
+    fn record(direction: Direction, frame: &str) -> CaptureRecord {
+        CaptureRecord::new(direction, frame.to_string())
+    }
+
+    #[test]
+    fn check_history_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = HistoryConfig {
+            load: 2,
+            ..HistoryConfig::default()
+        };
+        let mut store = HistoryStore::open(dir.path(), &config).unwrap();
+        for frame in ["bob MESSAGE one", "bob JOIN", "bob MESSAGE two"] {
+            store.append(&record(Direction::ToClient, frame)).unwrap();
+        }
+        store
+            .append(&record(Direction::ToServer, "alice MESSAGE sent"))
+            .unwrap();
+
+        // Reopening appends to the same segment and loads from it
+        let mut store = HistoryStore::open(dir.path(), &config).unwrap();
+        store
+            .append(&record(Direction::ToClient, "bob MESSAGE three"))
+            .unwrap();
+        let frames: Vec<_> = store
+            .recent_messages()
+            .unwrap()
+            .into_iter()
+            .map(|record| record.frame)
+            .collect();
+        assert_eq!(frames, ["bob MESSAGE two", "bob MESSAGE three"]);
+        assert_eq!(segments(dir.path()).unwrap().len(), 1);
+        assert_eq!(
+            store.recent_messages().unwrap()[0].payload,
+            parse_payload(String::from("bob MESSAGE two")).ok()
+        );
+    }
+
+    #[test]
+    fn check_history_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = HistoryConfig {
+            load: 1000,
+            max_size_mb: 2,
+            ..HistoryConfig::default()
+        };
+        let mut store = HistoryStore::open(dir.path(), &config).unwrap();
+        let frame = format!("bob MESSAGE {}", "x".repeat(500));
+        let line_size = serde_json::to_vec(&record(Direction::ToClient, &frame))
+            .unwrap()
+            .len() as u64;
+        // Enough lines for three full segments and a bit
+        let lines = SEGMENT_SIZE * 3 / line_size + 10;
+        for _ in 0..lines {
+            store.append(&record(Direction::ToClient, &frame)).unwrap();
+        }
+
+        let numbers: Vec<_> = segments(dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|(number, _)| number)
+            .collect();
+        assert_eq!(numbers, [2, 3, 4]);
+        assert_eq!(store.recent_messages().unwrap().len(), 1000);
+    }
+}
@@ -1,7 +1,14 @@
 pub mod app;
 pub mod args;
+pub mod bench;
+pub mod capture;
 pub mod client;
 pub mod codec;
 pub mod config;
+pub mod history;
 pub mod logger;
+#[cfg(feature = "test-support")]
+pub mod mock;
 pub mod protocol;
+pub mod proxy;
+pub mod server;
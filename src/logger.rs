@@ -4,16 +4,18 @@ use tui_logger::{TuiLoggerFile, TuiLoggerLevelOutput, set_default_level, set_log
 
 use crate::config::DspLogConfig;
 
-pub const NS_CONN: &'static str = "connection";
-pub const NS_CHAT: &'static str = "chat";
-pub const NS_APP: &'static str = "app";
+pub const NS_CONN: &str = "connection";
+pub const NS_CHAT: &str = "chat";
+pub const NS_APP: &str = "app";
+pub const NS_SERVER: &str = "server";
+pub const NS_PROXY: &str = "proxy";
+pub const NS_BENCH: &str = "bench";
 
 pub fn init_logger(log_config: &DspLogConfig) -> Result<()> {
-    tui_logger::init_logger(LevelFilter::Debug)
-        .with_context(|| format!("Failed to init TUI chat logger"))?;
+    tui_logger::init_logger(LevelFilter::Debug).context("Failed to init TUI chat logger")?;
     set_default_level(LevelFilter::Debug);
     if let Some(log_file) = &log_config.log_file {
-        let file_opts = TuiLoggerFile::new(&log_file)
+        let file_opts = TuiLoggerFile::new(log_file)
             .output_separator(' ')
             .output_timestamp(Some("%Y-%m-%dT%H:%M:%S".to_string()))
             .output_level(Some(TuiLoggerLevelOutput::Abbreviated))
@@ -24,3 +26,25 @@ pub fn init_logger(log_config: &DspLogConfig) -> Result<()> {
     }
     Ok(())
 }
+
+/// Logs to stderr for the server and proxy, `RUST_LOG` overrides the default info level
+pub fn init_stderr_logger() -> Result<()> {
+    stderr_logger()
+        .try_init()
+        .context("Failed to init server logger")
+}
+
+/// Stderr logger of the bench, which keeps an in-process server to warnings
+pub fn init_bench_logger() -> Result<()> {
+    stderr_logger()
+        .filter(Some(NS_SERVER), LevelFilter::Warn)
+        .parse_default_env()
+        .try_init()
+        .context("Failed to init bench logger")
+}
+
+fn stderr_logger() -> env_logger::Builder {
+    let mut builder = env_logger::Builder::new();
+    builder.filter_level(LevelFilter::Info).parse_default_env();
+    builder
+}
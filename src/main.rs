@@ -4,24 +4,33 @@ use std::error::Error;
 use uiop_dsp::args::*;
 use uiop_dsp::config::Config;
 use uiop_dsp::logger::init_logger;
-use uiop_dsp::{app::main::App, client::DspClient};
+use uiop_dsp::{
+    app::{main::App, replay::Replay},
+    client::DspClient,
+};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     // Parse CLI args
-    let args = Args::try_parse().with_context(|| format!("Invalid CLI arguments passed"))?;
+    let args = Args::try_parse().context("Invalid CLI arguments passed")?;
 
     // Build config
-    let config = Config::from_args(args);
+    let config = Config::load(args)?;
 
     // Init logger
-    let _ = init_logger(&config.log)?;
+    init_logger(&config.log)?;
 
-    // Start DSP client
-    let client = DspClient::start(&config.client).await?;
-
-    // Init chat app
-    let app = App::new(client.reader, client.writer, config.client);
+    // Init chat app, replaying a session or with a started DSP client
+    let app = match &config.replay {
+        Some(replay) => {
+            let replay = Replay::read(&replay.path, replay.speed)?;
+            App::replay(replay, config.client, config.ui)
+        }
+        None => {
+            let client = DspClient::start(&config.client).await?;
+            App::new(client.reader, client.writer, config.client, config.ui)
+        }
+    };
     app.start_with_crossterm()?;
 
     // App closed successfully, quitting
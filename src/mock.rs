@@ -0,0 +1,204 @@
+use crate::{
+    args::Args,
+    codec::{parse_payload, payload_bytes, read_buffer_until_frame},
+    config::{Config, DspClientConfig, FileConfig},
+    protocol::*,
+    server::SERVER_USERNAME,
+};
+
+use anyhow::{Context, Result, anyhow, ensure};
+use std::{future::Future, net::SocketAddr, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{
+        TcpListener,
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+    },
+    time::timeout,
+};
+
+/// How long a mock waits for a client before failing the test
+const EXPECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+async fn within<T>(what: &str, future: impl Future<Output = Result<T>>) -> Result<T> {
+    timeout(EXPECT_TIMEOUT, future)
+        .await
+        .map_err(|_| anyhow!("Timed out waiting for {}", what))?
+}
+
+/// Scriptable fake DSP server on an ephemeral local port, connections are accepted one at a time
+pub struct MockServer {
+    listener: TcpListener,
+}
+
+impl MockServer {
+    pub async fn start() -> Result<MockServer> {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .context("Failed to bind mock server")?;
+        Ok(MockServer { listener })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.listener.local_addr().unwrap()
+    }
+
+    /// Client config which connects to this server
+    pub fn client_config(&self, username: &str) -> DspClientConfig {
+        DspClientConfig {
+            server_address: self.address().to_string(),
+            username: username.to_string(),
+            recorder: None,
+            history: None,
+        }
+    }
+
+    /// Full app config which connects to this server, without history, mouse capture or
+    /// notifications, which would leak between tests or into their output
+    pub fn app_config(&self, username: &str) -> Result<Config> {
+        let args = Args {
+            server_address: self.address().to_string(),
+            username: username.to_string(),
+            log_file: None,
+            config: None,
+            no_mouse: true,
+            highlight_words: vec![],
+            record: None,
+            replay: None,
+            replay_speed: 1.0,
+            no_history: true,
+        };
+        let mut config = Config::from_parts(args, FileConfig::default())?;
+        config.ui.notify.do_not_disturb = true;
+        Ok(config)
+    }
+
+    pub async fn accept(&self) -> Result<MockConnection> {
+        let (stream, _) = within("a client to connect", async {
+            self.listener
+                .accept()
+                .await
+                .context("Failed to accept mock connection")
+        })
+        .await?;
+        let (reader, writer) = stream.into_split();
+        Ok(MockConnection {
+            reader: BufReader::new(reader),
+            writer,
+        })
+    }
+}
+
+/// Server side of a single client connection
+pub struct MockConnection {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+}
+
+impl MockConnection {
+    /// Next frame sent by the client, without its terminator
+    pub async fn next_frame(&mut self) -> Result<String> {
+        within("a frame", read_buffer_until_frame(&mut self.reader)).await
+    }
+
+    pub async fn next_payload(&mut self) -> Result<DspPayload> {
+        parse_payload(self.next_frame().await?)
+    }
+
+    /// Fails unless the client's next frame is the expected payload
+    pub async fn expect(&mut self, expected: DspPayload) -> Result<()> {
+        let payload = self.next_payload().await?;
+        ensure!(
+            payload == expected,
+            "Expected {:?}, received {:?}",
+            expected,
+            payload
+        );
+        Ok(())
+    }
+
+    /// Fails unless the client closes the connection without sending anything else
+    pub async fn expect_closed(&mut self) -> Result<()> {
+        let mut rest = vec![];
+        within("the client to disconnect", async {
+            self.reader
+                .read_to_end(&mut rest)
+                .await
+                .context("Failed to read until the client disconnected")
+        })
+        .await?;
+        ensure!(rest.is_empty(), "Received {:?} before disconnect", rest);
+        Ok(())
+    }
+
+    pub async fn send(&mut self, payload: DspPayload) -> Result<()> {
+        let mut bytes = payload_bytes(payload);
+        bytes.push(0u8);
+        self.send_raw(&bytes).await
+    }
+
+    /// Sends bytes as they are, e.g. malformed or partial frames
+    pub async fn send_raw(&mut self, bytes: &[u8]) -> Result<()> {
+        self.writer
+            .write_all(bytes)
+            .await
+            .context("Failed to send from mock server")
+    }
+
+    pub async fn send_error(&mut self, text: &str) -> Result<()> {
+        self.send(server_payload(DspMessage::ErrorMessage(ErrorMessage {
+            text: text.to_string(),
+        })))
+        .await
+    }
+
+    pub async fn send_challenge(&mut self, n: u64, phrase: &str) -> Result<()> {
+        self.send(server_payload(DspMessage::ChallengeMessage(
+            ChallengeMessage {
+                n,
+                phrase: phrase.to_string(),
+            },
+        )))
+        .await
+    }
+
+    pub async fn send_rescinded(&mut self) -> Result<()> {
+        self.send(server_payload(DspMessage::RescindedMessage(
+            RescindedMessage {},
+        )))
+        .await
+    }
+
+    /// Drops the connection like a crashed server would
+    pub fn disconnect(self) {}
+}
+
+pub fn join(username: &str) -> DspPayload {
+    DspPayload {
+        username: username.to_string(),
+        message: DspMessage::JoinMessage(JoinMessage {}),
+    }
+}
+
+pub fn quit(username: &str) -> DspPayload {
+    DspPayload {
+        username: username.to_string(),
+        message: DspMessage::QuitMessage(QuitMessage {}),
+    }
+}
+
+pub fn message(username: &str, text: &str) -> DspPayload {
+    DspPayload {
+        username: username.to_string(),
+        message: DspMessage::MessageMessage(MessageMessage {
+            text: text.to_string(),
+        }),
+    }
+}
+
+pub fn server_payload(message: DspMessage) -> DspPayload {
+    DspPayload {
+        username: SERVER_USERNAME.to_string(),
+        message,
+    }
+}
@@ -1,6 +1,7 @@
-use strum_macros::EnumString;
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumString};
 
-#[derive(Debug, PartialEq, EnumString)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, Display, Deserialize)]
 pub enum MessageType {
     JOIN,
     QUIT,
@@ -11,43 +12,157 @@ pub enum MessageType {
     ERROR,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct JoinMessage {}
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct QuitMessage {}
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MessageMessage {
     pub text: String,
 }
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ChallengeMessage {
     pub n: u64,
     pub phrase: String,
 }
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct RescindedMessage {}
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ResponseMessage {
     pub phrase: String,
 }
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ErrorMessage {
     pub text: String,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
 pub enum DspMessage {
+    #[serde(rename = "JOIN")]
     JoinMessage(JoinMessage),
+    #[serde(rename = "QUIT")]
     QuitMessage(QuitMessage),
+    #[serde(rename = "MESSAGE")]
     MessageMessage(MessageMessage),
+    #[serde(rename = "CHALLENGE")]
     ChallengeMessage(ChallengeMessage),
+    #[serde(rename = "RESCINDED")]
     RescindedMessage(RescindedMessage),
+    #[serde(rename = "RESPONSE")]
     ResponseMessage(ResponseMessage),
+    #[serde(rename = "ERROR")]
     ErrorMessage(ErrorMessage),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+impl DspMessage {
+    pub fn message_type(&self) -> MessageType {
+        match self {
+            DspMessage::JoinMessage(_) => MessageType::JOIN,
+            DspMessage::QuitMessage(_) => MessageType::QUIT,
+            DspMessage::MessageMessage(_) => MessageType::MESSAGE,
+            DspMessage::ChallengeMessage(_) => MessageType::CHALLENGE,
+            DspMessage::RescindedMessage(_) => MessageType::RESCINDED,
+            DspMessage::ResponseMessage(_) => MessageType::RESPONSE,
+            DspMessage::ErrorMessage(_) => MessageType::ERROR,
+        }
+    }
+
+    pub fn view(&self) -> DspMessageRef<'_> {
+        match self {
+            DspMessage::JoinMessage(_) => DspMessageRef::JoinMessage,
+            DspMessage::QuitMessage(_) => DspMessageRef::QuitMessage,
+            DspMessage::MessageMessage(m) => DspMessageRef::MessageMessage { text: &m.text },
+            DspMessage::ChallengeMessage(m) => DspMessageRef::ChallengeMessage {
+                n: m.n,
+                phrase: &m.phrase,
+            },
+            DspMessage::RescindedMessage(_) => DspMessageRef::RescindedMessage,
+            DspMessage::ResponseMessage(m) => DspMessageRef::ResponseMessage { phrase: &m.phrase },
+            DspMessage::ErrorMessage(m) => DspMessageRef::ErrorMessage { text: &m.text },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DspPayload {
     pub username: String,
     pub message: DspMessage,
 }
+
+impl DspPayload {
+    /// Borrowed view of the payload, for encoding without cloning it
+    pub fn view(&self) -> DspPayloadRef<'_> {
+        DspPayloadRef {
+            username: &self.username,
+            message: self.message.view(),
+        }
+    }
+}
+
+/// Message whose fields borrow from the frame it was parsed from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DspMessageRef<'a> {
+    JoinMessage,
+    QuitMessage,
+    MessageMessage { text: &'a str },
+    ChallengeMessage { n: u64, phrase: &'a str },
+    RescindedMessage,
+    ResponseMessage { phrase: &'a str },
+    ErrorMessage { text: &'a str },
+}
+
+impl DspMessageRef<'_> {
+    pub fn message_type(&self) -> MessageType {
+        match self {
+            DspMessageRef::JoinMessage => MessageType::JOIN,
+            DspMessageRef::QuitMessage => MessageType::QUIT,
+            DspMessageRef::MessageMessage { .. } => MessageType::MESSAGE,
+            DspMessageRef::ChallengeMessage { .. } => MessageType::CHALLENGE,
+            DspMessageRef::RescindedMessage => MessageType::RESCINDED,
+            DspMessageRef::ResponseMessage { .. } => MessageType::RESPONSE,
+            DspMessageRef::ErrorMessage { .. } => MessageType::ERROR,
+        }
+    }
+
+    pub fn to_message(&self) -> DspMessage {
+        match *self {
+            DspMessageRef::JoinMessage => DspMessage::JoinMessage(JoinMessage {}),
+            DspMessageRef::QuitMessage => DspMessage::QuitMessage(QuitMessage {}),
+            DspMessageRef::MessageMessage { text } => DspMessage::MessageMessage(MessageMessage {
+                text: text.to_string(),
+            }),
+            DspMessageRef::ChallengeMessage { n, phrase } => {
+                DspMessage::ChallengeMessage(ChallengeMessage {
+                    n,
+                    phrase: phrase.to_string(),
+                })
+            }
+            DspMessageRef::RescindedMessage => DspMessage::RescindedMessage(RescindedMessage {}),
+            DspMessageRef::ResponseMessage { phrase } => {
+                DspMessage::ResponseMessage(ResponseMessage {
+                    phrase: phrase.to_string(),
+                })
+            }
+            DspMessageRef::ErrorMessage { text } => DspMessage::ErrorMessage(ErrorMessage {
+                text: text.to_string(),
+            }),
+        }
+    }
+}
+
+/// Payload borrowing from its frame, only copied once it needs to outlive the frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DspPayloadRef<'a> {
+    pub username: &'a str,
+    pub message: DspMessageRef<'a>,
+}
+
+impl DspPayloadRef<'_> {
+    pub fn to_payload(&self) -> DspPayload {
+        DspPayload {
+            username: self.username.to_string(),
+            message: self.message.to_message(),
+        }
+    }
+}
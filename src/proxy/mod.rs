@@ -0,0 +1,224 @@
+mod rules;
+
+pub use rules::ProxyRules;
+
+use crate::{
+    capture::{CaptureRecord, Direction, Recorder},
+    codec::payload_bytes,
+    logger::NS_PROXY,
+};
+
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    time::sleep,
+};
+
+pub struct ProxyConfig {
+    pub server_address: String,
+    pub rules: ProxyRules,
+    pub capture: Option<Recorder>,
+}
+
+/// Forwards DSP frames between clients and a server, capturing and tampering with them
+pub struct DspProxy {
+    listener: TcpListener,
+    server_address: String,
+    rules: Arc<ProxyRules>,
+    capture: Option<Recorder>,
+}
+
+impl DspProxy {
+    pub async fn bind(address: &str, config: ProxyConfig) -> Result<DspProxy> {
+        let listener = TcpListener::bind(address)
+            .await
+            .with_context(|| format!("Failed to listen on '{}'", address))?;
+        Ok(DspProxy {
+            listener,
+            server_address: config.server_address,
+            rules: Arc::new(config.rules),
+            capture: config.capture,
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.listener
+            .local_addr()
+            .context("Failed to get the listening address")
+    }
+
+    /// Accepts clients until the listener fails, each gets its own server connection
+    pub async fn run(self) -> Result<()> {
+        let mut connection = 0;
+        loop {
+            connection += 1;
+            let (client, peer) = self
+                .listener
+                .accept()
+                .await
+                .context("Failed to accept a client connection")?;
+            info!(target: NS_PROXY, "Connection {} from {}", connection, peer);
+            let pipe = Pipe {
+                connection,
+                rules: self.rules.clone(),
+                capture: self.capture.clone(),
+            };
+            let server_address = self.server_address.clone();
+            tokio::spawn(async move {
+                if let Err(err) = pipe.serve(client, &server_address).await {
+                    warn!(target: NS_PROXY, "Connection {}: {:#}", connection, err);
+                }
+                info!(target: NS_PROXY, "Connection {} closed", connection);
+            });
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Pipe {
+    connection: u64,
+    rules: Arc<ProxyRules>,
+    capture: Option<Recorder>,
+}
+
+impl Pipe {
+    /// Forwards both ways until either side disconnects
+    async fn serve(self, client: TcpStream, server_address: &str) -> Result<()> {
+        let server = TcpStream::connect(server_address)
+            .await
+            .with_context(|| format!("Failed to connect to DSP server at '{}'", server_address))?;
+        let (client_reader, client_writer) = client.into_split();
+        let (server_reader, server_writer) = server.into_split();
+        tokio::select! {
+            result = self.clone().forward(Direction::ToServer, client_reader, server_writer) => result,
+            result = self.forward(Direction::ToClient, server_reader, client_writer) => result,
+        }
+    }
+
+    async fn forward(
+        self,
+        direction: Direction,
+        reader: impl AsyncRead + Unpin,
+        mut writer: impl AsyncWrite + Unpin,
+    ) -> Result<()> {
+        let mut reader = BufReader::new(reader);
+        loop {
+            let mut bytes = vec![];
+            let read_count = reader
+                .read_until(b'\0', &mut bytes)
+                .await
+                .context("Failed to read frame")?;
+            if read_count == 0 {
+                return Ok(());
+            }
+            if bytes.last() == Some(&0u8) {
+                bytes.pop();
+            }
+
+            let mut record = CaptureRecord::new(direction, String::from_utf8_lossy(&bytes).into());
+            record.connection = Some(self.connection);
+            let verdict = self
+                .rules
+                .apply(direction, &record.frame, record.payload.as_ref());
+            record.delay_ms = verdict.delay_ms;
+            record.dropped = verdict.drop;
+            if let Some(payload) = verdict.rewritten {
+                bytes = payload_bytes(payload);
+                record.rewritten = Some(String::from_utf8_lossy(&bytes).into());
+            }
+            debug!(target: NS_PROXY, "{:?} {}", direction, record.frame);
+            self.capture(&record);
+
+            if record.delay_ms > 0 {
+                sleep(Duration::from_millis(record.delay_ms)).await;
+            }
+            if record.dropped {
+                continue;
+            }
+            bytes.push(0u8);
+            writer
+                .write_all(&bytes)
+                .await
+                .context("Failed to forward frame")?;
+        }
+    }
+
+    fn capture(&self, record: &CaptureRecord) {
+        let Some(capture) = &self.capture else {
+            return;
+        };
+        if let Err(err) = capture.write(record) {
+            warn!(target: NS_PROXY, "{:#}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        capture::read_capture,
+        client::DspClient,
+        config::DspClientConfig,
+        protocol::*,
+        server::{DspServer, ServerConfig},
+    };
+
+    #[tokio::test]
+    async fn check_proxy_capture() {
+        let server = DspServer::bind("127.0.0.1:0", ServerConfig::default())
+            .await
+            .unwrap();
+        let server_address = server.local_addr().unwrap().to_string();
+        tokio::spawn(server.run());
+
+        let capture_file = tempfile::NamedTempFile::new().unwrap();
+        let config = ProxyConfig {
+            server_address,
+            rules: toml::from_str("[[rule]]\ncontains = \"spam\"\ndrop = true").unwrap(),
+            capture: Some(Recorder::create(capture_file.path()).unwrap()),
+        };
+        let proxy = DspProxy::bind("127.0.0.1:0", config).await.unwrap();
+        let proxy_address = proxy.local_addr().unwrap().to_string();
+        tokio::spawn(proxy.run());
+
+        let mut client = DspClient::start(&DspClientConfig {
+            server_address: proxy_address,
+            username: String::from("alice"),
+            recorder: None,
+            history: None,
+        })
+        .await
+        .unwrap();
+        let message = |text: &str| DspPayload {
+            username: String::from("alice"),
+            message: DspMessage::MessageMessage(MessageMessage {
+                text: text.to_string(),
+            }),
+        };
+        client.reader.read().await.unwrap();
+        client.writer.write(message("spam")).await.unwrap();
+        client.writer.write(message("hi")).await.unwrap();
+        assert_eq!(client.reader.read().await.unwrap(), message("hi"));
+
+        let records = read_capture(capture_file.path()).unwrap();
+        let frames: Vec<_> = records
+            .iter()
+            .map(|record| (record.direction, record.frame.as_str(), record.dropped))
+            .collect();
+        assert_eq!(
+            frames,
+            [
+                (Direction::ToServer, "alice JOIN", false),
+                (Direction::ToClient, "alice JOIN", false),
+                (Direction::ToServer, "alice MESSAGE spam", true),
+                (Direction::ToServer, "alice MESSAGE hi", false),
+                (Direction::ToClient, "alice MESSAGE hi", false),
+            ]
+        );
+        assert!(records.iter().all(|record| record.connection == Some(1)));
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{
+    capture::Direction,
+    protocol::{DspMessage, DspPayload, MessageType},
+};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{fs, path::Path};
+
+/// Chaos rules of the proxy, read from a TOML file with a `[[rule]]` table per rule
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProxyRules {
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<Rule>,
+}
+
+/// Every matching rule applies to a frame, conditions which are missing match anything
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub direction: Option<Direction>,
+    #[serde(rename = "type")]
+    pub message_type: Option<MessageType>,
+    pub username: Option<String>,
+    /// Text the frame has to contain
+    pub contains: Option<String>,
+    /// Chance of applying to a matching frame, from 0 to 1
+    #[serde(default = "always")]
+    pub probability: f64,
+    /// Milliseconds to hold the frame back, frames after it wait too
+    #[serde(default)]
+    pub delay_ms: u64,
+    #[serde(default)]
+    pub drop: bool,
+    pub rewrite: Option<Rewrite>,
+}
+
+/// Fields replaced in a decoded frame
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rewrite {
+    pub username: Option<String>,
+    /// Text of a MESSAGE or ERROR, phrase of a CHALLENGE or RESPONSE
+    pub text: Option<String>,
+}
+
+fn always() -> f64 {
+    1.0
+}
+
+/// What the rules do with a single frame
+#[derive(Debug, Default, PartialEq)]
+pub struct Verdict {
+    pub delay_ms: u64,
+    pub drop: bool,
+    /// Replaces the frame when set
+    pub rewritten: Option<DspPayload>,
+}
+
+impl ProxyRules {
+    pub fn read(path: &Path) -> Result<ProxyRules> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read rules file '{}'", path.display()))?;
+        toml::from_str(&text)
+            .with_context(|| format!("Failed to parse rules file '{}'", path.display()))
+    }
+
+    pub fn apply(
+        &self,
+        direction: Direction,
+        frame: &str,
+        payload: Option<&DspPayload>,
+    ) -> Verdict {
+        let mut verdict = Verdict::default();
+        for rule in &self.rules {
+            if !rule.matches(direction, frame, payload) {
+                continue;
+            }
+            if rule.probability < 1.0 && rand::random::<f64>() >= rule.probability {
+                continue;
+            }
+            verdict.delay_ms += rule.delay_ms;
+            verdict.drop |= rule.drop;
+            if let Some(rewrite) = &rule.rewrite {
+                let current = verdict.rewritten.as_ref().or(payload);
+                verdict.rewritten = current.map(|payload| rewrite.apply(payload.clone()));
+            }
+        }
+        verdict
+    }
+}
+
+impl Rule {
+    fn matches(&self, direction: Direction, frame: &str, payload: Option<&DspPayload>) -> bool {
+        let message_type = payload.map(|payload| payload.message.message_type());
+        let username = payload.map(|payload| payload.username.as_str());
+        self.direction.is_none_or(|expected| expected == direction)
+            && self
+                .message_type
+                .is_none_or(|expected| Some(expected) == message_type)
+            && self
+                .username
+                .as_deref()
+                .is_none_or(|expected| Some(expected) == username)
+            && self
+                .contains
+                .as_deref()
+                .is_none_or(|text| frame.contains(text))
+    }
+}
+
+impl Rewrite {
+    fn apply(&self, mut payload: DspPayload) -> DspPayload {
+        if let Some(username) = &self.username {
+            payload.username = username.clone();
+        }
+        if let Some(text) = &self.text {
+            let field = match &mut payload.message {
+                DspMessage::MessageMessage(m) => Some(&mut m.text),
+                DspMessage::ErrorMessage(m) => Some(&mut m.text),
+                DspMessage::ChallengeMessage(m) => Some(&mut m.phrase),
+                DspMessage::ResponseMessage(m) => Some(&mut m.phrase),
+                _ => None,
+            };
+            if let Some(field) = field {
+                *field = text.clone();
+            }
+        }
+        payload
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::parse_payload;
+
+    #[test]
+    fn check_rules() {
+        let rules: ProxyRules = toml::from_str(
+            r#"
+            [[rule]]
+            direction = "to_client"
+            type = "MESSAGE"
+            username = "bob"
+            rewrite = { username = "mallory", text = "rewritten" }
+
+            [[rule]]
+            contains = "spam"
+            drop = true
+
+            [[rule]]
+            type = "JOIN"
+            delay_ms = 250
+            "#,
+        )
+        .unwrap();
+
+        let verdict = |direction, frame: &str| {
+            let payload = parse_payload(frame.to_string()).ok();
+            rules.apply(direction, frame, payload.as_ref())
+        };
+        assert_eq!(
+            verdict(Direction::ToClient, "bob MESSAGE hi").rewritten,
+            parse_payload(String::from("mallory MESSAGE rewritten")).ok()
+        );
+        assert_eq!(
+            verdict(Direction::ToServer, "bob MESSAGE hi"),
+            Verdict::default()
+        );
+        assert!(verdict(Direction::ToServer, "alice MESSAGE spam").drop);
+        assert!(verdict(Direction::ToServer, "spam garbage").drop);
+        assert_eq!(verdict(Direction::ToClient, "alice JOIN").delay_ms, 250);
+    }
+}
@@ -0,0 +1,87 @@
+use rand::{Rng, distr::Alphanumeric};
+use sha2::{Digest, Sha256};
+use std::time::Instant;
+
+use crate::protocol::ChallengeMessage;
+
+const PHRASE_LENGTH: usize = 16;
+/// Longest RESPONSE phrase allowed by the spec
+const MAX_RESPONSE_LENGTH: usize = 512;
+
+/// Proof of work a rate limited client has to send a RESPONSE to
+#[derive(Debug)]
+pub struct Challenge {
+    /// Leading hex zeros the SHA-256 of the response needs
+    pub difficulty: u64,
+    pub phrase: String,
+    /// The client is disconnected when no response arrives by then
+    pub deadline: Instant,
+}
+
+impl Challenge {
+    pub fn new(difficulty: u64, deadline: Instant) -> Challenge {
+        let phrase = rand::rng()
+            .sample_iter(Alphanumeric)
+            .take(PHRASE_LENGTH)
+            .map(char::from)
+            .collect();
+        Challenge {
+            difficulty,
+            phrase,
+            deadline,
+        }
+    }
+
+    pub fn message(&self) -> ChallengeMessage {
+        ChallengeMessage {
+            n: self.difficulty,
+            phrase: self.phrase.clone(),
+        }
+    }
+
+    /// Whether a response starts with the phrase and hashes to enough leading zeros
+    pub fn verify(&self, response: &str) -> bool {
+        response.len() <= MAX_RESPONSE_LENGTH
+            && response.chars().all(|c| c.is_ascii_graphic())
+            && response.starts_with(&self.phrase)
+            && leading_hex_zeros(response) >= self.difficulty
+    }
+}
+
+/// Leading zeros of the hex SHA-256 digest of the input
+pub fn leading_hex_zeros(input: &str) -> u64 {
+    let mut zeros = 0;
+    for byte in Sha256::digest(input.as_bytes()) {
+        match byte {
+            0 => zeros += 2,
+            1..0x10 => return zeros + 1,
+            _ => return zeros,
+        }
+    }
+    zeros
+}
+
+/// RESPONSE phrase to a challenge, the challenge phrase followed by the first counter that works
+pub fn solve(challenge: &ChallengeMessage) -> String {
+    (0u64..)
+        .map(|nonce| format!("{}{}", challenge.phrase, nonce))
+        .find(|response| leading_hex_zeros(response) >= challenge.n)
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_challenge_verify() {
+        assert_eq!(leading_hex_zeros("abc"), 0);
+        let challenge = Challenge::new(2, Instant::now());
+        assert_eq!(challenge.phrase.len(), PHRASE_LENGTH);
+
+        let response = solve(&challenge.message());
+        assert!(challenge.verify(&response));
+        assert!(!challenge.verify(&response[1..]));
+        assert!(!challenge.verify(&format!("{} x", response)));
+    }
+}
@@ -0,0 +1,66 @@
+use std::time::Instant;
+
+/// Allows bursts of frames up to its capacity, refilled at a steady rate
+#[derive(Debug)]
+pub struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    /// Tokens added per second
+    rate: f64,
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(capacity: u32, rate: f64, now: Instant) -> TokenBucket {
+        TokenBucket {
+            capacity: capacity as f64,
+            tokens: capacity as f64,
+            rate,
+            refilled: now,
+        }
+    }
+
+    /// Takes a token if one is left
+    pub fn try_take(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+
+    pub fn fill(&mut self, now: Instant) {
+        self.tokens = self.capacity;
+        self.refilled = now;
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.refilled = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn check_token_bucket() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(3, 2.0, start);
+        assert!((0..3).all(|_| bucket.try_take(start)));
+        assert!(!bucket.try_take(start));
+
+        let later = start + Duration::from_millis(1250);
+        assert!(bucket.try_take(later));
+        assert!(bucket.try_take(later));
+        assert!(!bucket.try_take(later));
+
+        bucket.fill(later);
+        assert!((0..3).all(|_| bucket.try_take(later)));
+        assert!(!bucket.try_take(later + Duration::from_millis(100)));
+    }
+}
@@ -0,0 +1,633 @@
+mod challenge;
+mod limit;
+
+pub use challenge::solve as solve_challenge;
+
+use crate::{
+    client::DspWriter,
+    codec::{max_frame_length, parse_frame, read_frame_limited},
+    logger::NS_SERVER,
+    protocol::*,
+};
+
+use anyhow::{Context, Result, anyhow};
+use challenge::Challenge;
+use limit::TokenBucket;
+use log::{debug, info};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    ops::ControlFlow,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::BufReader,
+    net::{TcpListener, TcpStream, tcp::OwnedReadHalf},
+    sync::{
+        Notify,
+        mpsc::{self, Sender, error::TrySendError},
+    },
+    time::timeout_at,
+};
+
+/// Username of payloads sent by the server itself, clients can't join with it
+pub const SERVER_USERNAME: &str = "server";
+
+/// Longest text of a client's MESSAGE in characters
+const MAX_MESSAGE_LENGTH: usize = 512;
+/// Longest frame accepted from a client, the message length is still checked in characters
+const MAX_FRAME_LENGTH: usize = max_frame_length(MAX_MESSAGE_LENGTH);
+
+/// Rate limiting of each connection
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Frames a client can send at once before being challenged
+    pub burst: u32,
+    /// Frames per second a client can keep sending
+    pub rate: f64,
+    /// Leading zeros of a connection's first challenge, each further one needs another zero
+    pub difficulty: u64,
+    pub max_difficulty: u64,
+    /// Clients which don't respond to a challenge in time are disconnected
+    pub challenge_timeout: Duration,
+    /// Payloads queued for a client before it's disconnected for not keeping up
+    pub outbox_capacity: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            burst: 10,
+            rate: 2.0,
+            difficulty: 4,
+            max_difficulty: 7,
+            challenge_timeout: Duration::from_secs(60),
+            outbox_capacity: 1024,
+        }
+    }
+}
+
+/// Joined clients by username
+type Clients = Arc<Mutex<HashMap<String, Client>>>;
+
+/// Queues payloads for a connection's writer, and disconnects it once the queue is full
+#[derive(Clone)]
+struct Client {
+    outbox: Sender<DspPayload>,
+    lagging: Arc<Notify>,
+}
+
+impl Client {
+    fn deliver(&self, payload: DspPayload) {
+        match self.outbox.try_send(payload) {
+            Ok(()) | Err(TrySendError::Closed(_)) => {}
+            Err(TrySendError::Full(_)) => self.lagging.notify_one(),
+        }
+    }
+}
+
+/// Reference DSP server, which broadcasts the messages of joined clients to all of them
+pub struct DspServer {
+    listener: TcpListener,
+    clients: Clients,
+    config: Arc<ServerConfig>,
+}
+
+impl DspServer {
+    pub async fn bind(address: &str, config: ServerConfig) -> Result<DspServer> {
+        let listener = TcpListener::bind(address)
+            .await
+            .with_context(|| format!("Failed to listen on '{}'", address))?;
+        Ok(DspServer {
+            listener,
+            clients: Clients::default(),
+            config: Arc::new(config),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.listener
+            .local_addr()
+            .context("Failed to get the listening address")
+    }
+
+    /// Accepts clients until the listener fails, each one is served by its own task
+    pub async fn run(self) -> Result<()> {
+        loop {
+            let (stream, peer) = self
+                .listener
+                .accept()
+                .await
+                .context("Failed to accept a client connection")?;
+            debug!(target: NS_SERVER, "Accepted connection from {}", peer);
+            let clients = self.clients.clone();
+            let config = self.config.clone();
+            tokio::spawn(async move {
+                Connection::serve(stream, clients, config).await;
+                debug!(target: NS_SERVER, "Closed connection from {}", peer);
+            });
+        }
+    }
+}
+
+struct Connection {
+    reader: BufReader<OwnedReadHalf>,
+    client: Client,
+    clients: Clients,
+    config: Arc<ServerConfig>,
+    /// Set once the client has joined
+    username: Option<String>,
+    bucket: TokenBucket,
+    /// Frames are dropped while a challenge is pending
+    challenge: Option<Challenge>,
+    /// Challenges issued so far, which raise the difficulty of the next one
+    challenges: u64,
+}
+
+impl Connection {
+    async fn serve(stream: TcpStream, clients: Clients, config: Arc<ServerConfig>) {
+        let (reader, writer) = stream.into_split();
+        let (outbox, mut receiver) = mpsc::channel(config.outbox_capacity);
+        let sending = tokio::spawn(async move {
+            let mut writer = DspWriter::new(writer);
+            while let Some(payload) = receiver.recv().await {
+                if let Err(err) = writer.write(payload).await {
+                    debug!(target: NS_SERVER, "{:#}", err);
+                    break;
+                }
+            }
+        });
+
+        let lagging = Arc::new(Notify::new());
+        let mut connection = Connection {
+            reader: BufReader::new(reader),
+            client: Client {
+                outbox,
+                lagging: lagging.clone(),
+            },
+            clients,
+            bucket: TokenBucket::new(config.burst, config.rate, Instant::now()),
+            config,
+            username: None,
+            challenge: None,
+            challenges: 0,
+        };
+        let mut frame = vec![];
+        let mut lagged = false;
+        loop {
+            let deadline = connection.challenge.as_ref().map(|c| c.deadline);
+            let read = read_frame_limited(&mut connection.reader, &mut frame, MAX_FRAME_LENGTH);
+            let read = async {
+                match deadline {
+                    Some(deadline) => timeout_at(deadline.into(), read).await.ok(),
+                    None => Some(read.await),
+                }
+            };
+            let read = tokio::select! {
+                read = read => read,
+                _ = lagging.notified() => {
+                    lagged = true;
+                    break;
+                }
+            };
+            let Some(read) = read else {
+                connection.reply_error("Challenge response timed out");
+                break;
+            };
+            let fits = match read {
+                Ok(fits) => fits,
+                Err(err) => {
+                    debug!(target: NS_SERVER, "{:#}", err);
+                    break;
+                }
+            };
+            if connection.handle_frame(fits.then_some(&frame)).is_break() {
+                break;
+            }
+        }
+        connection.leave();
+        drop(connection);
+
+        // Queued payloads are still sent before the connection closes, unless it couldn't keep up
+        if lagged {
+            info!(target: NS_SERVER, "Disconnecting a client which doesn't keep up");
+            sending.abort();
+        } else {
+            let _ = sending.await;
+        }
+    }
+
+    /// Handles a frame, which is `None` when it was longer than [`MAX_FRAME_LENGTH`]
+    fn handle_frame(&mut self, frame: Option<&[u8]>) -> ControlFlow<()> {
+        let payload = match frame {
+            Some(frame) => parse_frame(frame).map(|payload| payload.to_payload()),
+            None => Err(anyhow!("Message is too long")),
+        };
+
+        // Responses to a pending challenge and quits are never limited, everything else is dropped
+        // during a challenge
+        match &payload {
+            Ok(DspPayload {
+                message: DspMessage::ResponseMessage(response),
+                ..
+            }) if self.challenge.is_some() => return self.respond(&response.phrase),
+            Ok(DspPayload {
+                message: DspMessage::QuitMessage(_),
+                ..
+            }) => {}
+            _ if self.challenge.is_some() => {
+                debug!(target: NS_SERVER, "Dropping a frame during a challenge");
+                return ControlFlow::Continue(());
+            }
+            _ if !self.bucket.try_take(Instant::now()) => {
+                self.issue_challenge();
+                return ControlFlow::Continue(());
+            }
+            _ => {}
+        }
+
+        let payload = match payload {
+            Ok(payload) => payload,
+            Err(err) => {
+                debug!(target: NS_SERVER, "{:#}", err);
+                match frame {
+                    Some(_) => self.reply_error("Invalid message"),
+                    None => self.reply_error("Message is too long"),
+                }
+                return ControlFlow::Continue(());
+            }
+        };
+
+        match (&self.username, payload.message) {
+            (None, DspMessage::JoinMessage(_)) => self.join(payload.username),
+            (None, _) => self.reply_error("Join the server first"),
+            (Some(username), _) if *username != payload.username => {
+                self.reply_error("Username doesn't match the one you joined with")
+            }
+            (Some(_), DspMessage::JoinMessage(_)) => self.reply_error("Already joined"),
+            (Some(_), DspMessage::MessageMessage(message)) => {
+                if message.text.chars().count() > MAX_MESSAGE_LENGTH {
+                    self.reply_error("Message is too long");
+                } else {
+                    self.broadcast(DspPayload {
+                        username: payload.username,
+                        message: DspMessage::MessageMessage(message),
+                    });
+                }
+            }
+            (Some(_), DspMessage::QuitMessage(_)) => return ControlFlow::Break(()),
+            (Some(_), DspMessage::ResponseMessage(_)) => {
+                self.reply_error("No challenge is pending")
+            }
+            (Some(_), _) => self.reply_error("Unexpected message type"),
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn issue_challenge(&mut self) {
+        let difficulty = (self.config.difficulty + self.challenges).min(self.config.max_difficulty);
+        let challenge = Challenge::new(difficulty, Instant::now() + self.config.challenge_timeout);
+        self.challenges += 1;
+        info!(
+            target: NS_SERVER,
+            "Rate limiting {}, challenge difficulty {}",
+            self.username.as_deref().unwrap_or("a client which hasn't joined"),
+            difficulty
+        );
+        self.send(DspMessage::ChallengeMessage(challenge.message()));
+        self.challenge = Some(challenge);
+    }
+
+    /// Rescinds the pending challenge on a valid response, wrong responses disconnect the client
+    fn respond(&mut self, response: &str) -> ControlFlow<()> {
+        let Some(challenge) = self.challenge.take() else {
+            return ControlFlow::Continue(());
+        };
+        if !challenge.verify(response) {
+            self.reply_error("Wrong challenge response");
+            return ControlFlow::Break(());
+        }
+        self.bucket.fill(Instant::now());
+        self.send(DspMessage::RescindedMessage(RescindedMessage {}));
+        ControlFlow::Continue(())
+    }
+
+    fn join(&mut self, username: String) {
+        {
+            let mut clients = self.clients.lock().unwrap();
+            if username == SERVER_USERNAME || clients.contains_key(&username) {
+                drop(clients);
+                self.reply_error(&format!("Username '{}' is already taken", username));
+                return;
+            }
+            clients.insert(username.clone(), self.client.clone());
+        }
+        info!(target: NS_SERVER, "User '{}' joined", username);
+        self.username = Some(username.clone());
+        self.broadcast(DspPayload {
+            username,
+            message: DspMessage::JoinMessage(JoinMessage {}),
+        });
+    }
+
+    /// Announces that a joined client quit or disconnected
+    fn leave(&mut self) {
+        let Some(username) = self.username.take() else {
+            return;
+        };
+        self.clients.lock().unwrap().remove(&username);
+        info!(target: NS_SERVER, "User '{}' quit", username);
+        self.broadcast(DspPayload {
+            username,
+            message: DspMessage::QuitMessage(QuitMessage {}),
+        });
+    }
+
+    /// Sends a payload to every joined client, including the sender
+    fn broadcast(&self, payload: DspPayload) {
+        for client in self.clients.lock().unwrap().values() {
+            client.deliver(payload.clone());
+        }
+    }
+
+    fn reply_error(&self, text: &str) {
+        info!(target: NS_SERVER, "Replying with error: {}", text);
+        self.send(DspMessage::ErrorMessage(ErrorMessage {
+            text: text.to_string(),
+        }));
+    }
+
+    /// Sends a message from the server to this client only
+    fn send(&self, message: DspMessage) {
+        self.client.deliver(DspPayload {
+            username: SERVER_USERNAME.to_string(),
+            message,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        client::{DspClient, DspReader},
+        config::DspClientConfig,
+    };
+    use tokio::{io::AsyncWriteExt, net::TcpSocket};
+
+    async fn join(address: SocketAddr, username: &str) -> DspClient {
+        DspClient::start(&DspClientConfig {
+            server_address: address.to_string(),
+            username: username.to_string(),
+            recorder: None,
+            history: None,
+        })
+        .await
+        .unwrap()
+    }
+
+    fn payload(username: &str, message: DspMessage) -> DspPayload {
+        DspPayload {
+            username: username.to_string(),
+            message,
+        }
+    }
+
+    #[tokio::test]
+    async fn check_join_and_broadcast() {
+        let server = DspServer::bind("127.0.0.1:0", ServerConfig::default())
+            .await
+            .unwrap();
+        let address = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let join_message = || DspMessage::JoinMessage(JoinMessage {});
+        let mut alice = join(address, "alice").await;
+        assert_eq!(
+            alice.reader.read().await.unwrap(),
+            payload("alice", join_message())
+        );
+        let mut bob = join(address, "bob").await;
+        assert_eq!(
+            bob.reader.read().await.unwrap(),
+            payload("bob", join_message())
+        );
+        assert_eq!(
+            alice.reader.read().await.unwrap(),
+            payload("bob", join_message())
+        );
+
+        let mut taken = join(address, "alice").await;
+        let error = DspMessage::ErrorMessage(ErrorMessage {
+            text: String::from("Username 'alice' is already taken"),
+        });
+        assert_eq!(taken.reader.read().await.unwrap(), payload("server", error));
+
+        let message = DspMessage::MessageMessage(MessageMessage {
+            text: String::from("hi"),
+        });
+        bob.writer
+            .write(payload("bob", message.clone()))
+            .await
+            .unwrap();
+        assert_eq!(
+            alice.reader.read().await.unwrap(),
+            payload("bob", message.clone())
+        );
+        assert_eq!(bob.reader.read().await.unwrap(), payload("bob", message));
+
+        drop(bob);
+        let quit = DspMessage::QuitMessage(QuitMessage {});
+        assert_eq!(alice.reader.read().await.unwrap(), payload("bob", quit));
+    }
+
+    #[tokio::test]
+    async fn check_rate_limit_challenge() {
+        let config = ServerConfig {
+            burst: 2,
+            rate: 0.01,
+            difficulty: 1,
+            ..ServerConfig::default()
+        };
+        let server = DspServer::bind("127.0.0.1:0", config).await.unwrap();
+        let address = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let mut alice = join(address, "alice").await;
+        alice.reader.read().await.unwrap();
+        let message = |text: &str| {
+            let text = text.to_string();
+            payload("alice", DspMessage::MessageMessage(MessageMessage { text }))
+        };
+        alice.writer.write(message("first")).await.unwrap();
+        assert_eq!(alice.reader.read().await.unwrap(), message("first"));
+
+        alice.writer.write(message("limited")).await.unwrap();
+        alice.writer.write(message("dropped")).await.unwrap();
+        let DspMessage::ChallengeMessage(challenge) = alice.reader.read().await.unwrap().message
+        else {
+            panic!("Expected a challenge");
+        };
+        assert_eq!(challenge.n, 1);
+        let phrase = solve_challenge(&challenge);
+        let response = DspMessage::ResponseMessage(ResponseMessage { phrase });
+        alice
+            .writer
+            .write(payload("alice", response))
+            .await
+            .unwrap();
+        let rescinded = DspMessage::RescindedMessage(RescindedMessage {});
+        assert_eq!(
+            alice.reader.read().await.unwrap(),
+            payload("server", rescinded)
+        );
+
+        alice.writer.write(message("again")).await.unwrap();
+        assert_eq!(alice.reader.read().await.unwrap(), message("again"));
+    }
+
+    #[tokio::test]
+    async fn check_unsolicited_responses_limited() {
+        let config = ServerConfig {
+            burst: 2,
+            rate: 0.01,
+            difficulty: 1,
+            ..ServerConfig::default()
+        };
+        let server = DspServer::bind("127.0.0.1:0", config).await.unwrap();
+        let address = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let mut alice = join(address, "alice").await;
+        alice.reader.read().await.unwrap();
+        for _ in 0..3 {
+            let phrase = String::from("unsolicited");
+            let response = DspMessage::ResponseMessage(ResponseMessage { phrase });
+            alice
+                .writer
+                .write(payload("alice", response))
+                .await
+                .unwrap();
+        }
+        let error = DspMessage::ErrorMessage(ErrorMessage {
+            text: String::from("No challenge is pending"),
+        });
+        assert_eq!(alice.reader.read().await.unwrap(), payload("server", error));
+        let message = alice.reader.read().await.unwrap().message;
+        assert!(matches!(message, DspMessage::ChallengeMessage(_)));
+    }
+
+    #[tokio::test]
+    async fn check_oversized_frame() {
+        let server = DspServer::bind("127.0.0.1:0", ServerConfig::default())
+            .await
+            .unwrap();
+        let address = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let (reader, mut writer) = TcpStream::connect(address).await.unwrap().into_split();
+        let mut reader = DspReader::new(reader);
+        writer.write_all(b"alice JOIN\0").await.unwrap();
+        reader.read().await.unwrap();
+
+        // Streamed without a terminator, the server skips it instead of buffering it
+        let chunk = [b'x'; 64 * 1024];
+        for _ in 0..16 {
+            writer.write_all(&chunk).await.unwrap();
+        }
+        writer.write_all(b"\0alice MESSAGE hi\0").await.unwrap();
+        let error = DspMessage::ErrorMessage(ErrorMessage {
+            text: String::from("Message is too long"),
+        });
+        assert_eq!(reader.read().await.unwrap(), payload("server", error));
+        let message = DspMessage::MessageMessage(MessageMessage {
+            text: String::from("hi"),
+        });
+        assert_eq!(reader.read().await.unwrap(), payload("alice", message));
+    }
+
+    #[tokio::test]
+    async fn check_message_length_in_chars() {
+        let server = DspServer::bind("127.0.0.1:0", ServerConfig::default())
+            .await
+            .unwrap();
+        let address = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let mut alice = join(address, "alice").await;
+        alice.reader.read().await.unwrap();
+        let message = |length: usize| {
+            let text = "🦀".repeat(length);
+            payload("alice", DspMessage::MessageMessage(MessageMessage { text }))
+        };
+        alice
+            .writer
+            .write(message(MAX_MESSAGE_LENGTH))
+            .await
+            .unwrap();
+        assert_eq!(
+            alice.reader.read().await.unwrap(),
+            message(MAX_MESSAGE_LENGTH)
+        );
+
+        alice
+            .writer
+            .write(message(MAX_MESSAGE_LENGTH + 1))
+            .await
+            .unwrap();
+        let error = DspMessage::ErrorMessage(ErrorMessage {
+            text: String::from("Message is too long"),
+        });
+        assert_eq!(alice.reader.read().await.unwrap(), payload("server", error));
+    }
+
+    #[tokio::test]
+    async fn check_lagging_client_disconnected() {
+        let config = ServerConfig {
+            burst: 100_000,
+            rate: 100_000.0,
+            outbox_capacity: 256,
+            ..ServerConfig::default()
+        };
+        let server = DspServer::bind("127.0.0.1:0", config).await.unwrap();
+        let address = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        // Alice joins with a tiny receive window and then stops reading
+        let socket = TcpSocket::new_v4().unwrap();
+        socket.set_recv_buffer_size(4096).unwrap();
+        let (reader, mut writer) = socket.connect(address).await.unwrap().into_split();
+        let mut alice = DspReader::new(reader);
+        writer.write_all(b"alice JOIN\0").await.unwrap();
+        alice.read().await.unwrap();
+
+        // Bob keeps up by reading back each of his messages before sending the next one
+        let mut bob = join(address, "bob").await;
+        bob.reader.read().await.unwrap();
+        let message = payload(
+            "bob",
+            DspMessage::MessageMessage(MessageMessage {
+                text: "x".repeat(MAX_MESSAGE_LENGTH),
+            }),
+        );
+        let quit = payload("alice", DspMessage::QuitMessage(QuitMessage {}));
+        let mut disconnected = false;
+        'flood: for _ in 0..10_000 {
+            bob.writer.write(message.clone()).await.unwrap();
+            loop {
+                let received = bob.reader.read().await.unwrap();
+                if received == quit {
+                    disconnected = true;
+                    break 'flood;
+                } else if received == message {
+                    break;
+                }
+            }
+        }
+        assert!(disconnected, "Alice wasn't disconnected");
+
+        // Whatever reached alice's socket before, the server closed it afterwards
+        while alice.read().await.is_ok() {}
+    }
+}
@@ -0,0 +1,281 @@
+use std::{sync::mpsc::Receiver, time::Duration};
+
+use uiop_dsp::{
+    app::{
+        command::Command, event::AppEvent, replay::Replay, state::AppState, status::ConnectionState,
+    },
+    capture::{Direction, Recorder, read_capture},
+    client::DspClient,
+    config::{Config, FileConfig},
+    history::{History, HistoryConfig},
+    mock::{MockConnection, MockServer, join, message},
+};
+
+async fn start_app(server: &MockServer) -> (AppState, Receiver<AppEvent>, MockConnection) {
+    start_app_with(server, server.app_config("alice").unwrap()).await
+}
+
+async fn start_app_with(
+    server: &MockServer,
+    config: Config,
+) -> (AppState, Receiver<AppEvent>, MockConnection) {
+    let client = DspClient::start(&config.client).await.unwrap();
+    let mut connection = server.accept().await.unwrap();
+    connection.expect(join("alice")).await.unwrap();
+
+    let mut state = AppState::new(client.reader, client.writer, config.client, config.ui);
+    let events = state.start_headless().unwrap();
+    (state, events, connection)
+}
+
+/// Handles app events until one matches, rerenders are skipped
+fn handle_until(state: &mut AppState, events: &Receiver<AppEvent>, done: fn(&AppEvent) -> bool) {
+    loop {
+        let event = events.recv_timeout(Duration::from_secs(5)).unwrap();
+        let matched = done(&event);
+        state.handle_app_event(event);
+        if matched {
+            return;
+        }
+    }
+}
+
+fn last_line(state: &AppState) -> String {
+    state.chat.lines().last().unwrap().to_string()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn check_receive_and_reconnect() {
+    let server = MockServer::start().await.unwrap();
+    let (mut state, events, mut connection) = start_app(&server).await;
+    let received = |event: &AppEvent| matches!(event, AppEvent::PayloadReceived(_));
+
+    connection.send(message("bob", "hi alice")).await.unwrap();
+    handle_until(&mut state, &events, received);
+    assert_eq!(last_line(&state), "[bob] hi alice");
+
+    connection.disconnect();
+    handle_until(&mut state, &events, |event| {
+        matches!(event, AppEvent::Disconnected(_))
+    });
+    assert_eq!(state.status.connection, ConnectionState::Disconnected);
+
+    state.reconnect();
+    let mut connection = server.accept().await.unwrap();
+    connection.expect(join("alice")).await.unwrap();
+    handle_until(&mut state, &events, |event| {
+        matches!(event, AppEvent::Reconnected(_))
+    });
+    assert_eq!(state.status.connection, ConnectionState::Connected);
+
+    connection
+        .send(message("bob", "welcome back"))
+        .await
+        .unwrap();
+    handle_until(&mut state, &events, received);
+    assert_eq!(last_line(&state), "[bob] welcome back");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn check_mentions_counted() {
+    let server = MockServer::start().await.unwrap();
+    let (mut state, events, mut connection) = start_app(&server).await;
+    let received = |event: &AppEvent| matches!(event, AppEvent::PayloadReceived(_));
+
+    connection
+        .send(message("bob", "alice? @alice!"))
+        .await
+        .unwrap();
+    handle_until(&mut state, &events, received);
+    connection.send(message("bob", "hi alice")).await.unwrap();
+    handle_until(&mut state, &events, received);
+    assert_eq!(state.unread_mentions, 3);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn check_tabs_follow_layout() {
+    let server = MockServer::start().await.unwrap();
+    let (mut state, _events, _connection) = start_app(&server).await;
+
+    state.resize(80, 24);
+    state.next_tab();
+    assert_eq!(state.selected_tab, 1);
+    // Shrinking into the compact layout hides the tabs and goes back to the prompt
+    state.resize(40, 12);
+    assert_eq!(state.selected_tab, 0);
+    state.next_tab();
+    assert_eq!(state.selected_tab, 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn check_send_while_reconnecting() {
+    let server = MockServer::start().await.unwrap();
+    let (mut state, events, mut connection) = start_app(&server).await;
+
+    state.reconnect();
+    state.send_message(String::from("queued"));
+    // The old connection is closed without the queued message
+    connection.expect_closed().await.unwrap();
+
+    let mut connection = server.accept().await.unwrap();
+    connection.expect(join("alice")).await.unwrap();
+    handle_until(&mut state, &events, |event| {
+        matches!(event, AppEvent::Reconnected(_))
+    });
+    connection.expect(message("alice", "queued")).await.unwrap();
+    handle_until(&mut state, &events, |event| {
+        matches!(event, AppEvent::PayloadSent(_))
+    });
+
+    state.send_message(String::from("after"));
+    connection.expect(message("alice", "after")).await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn check_filters_saved_on_change() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    let server = MockServer::start().await.unwrap();
+    let mut config = server.app_config("alice").unwrap();
+    config.ui.config_path = Some(path.clone());
+    let (mut state, _events, _connection) = start_app_with(&server, config).await;
+
+    // Failed commands don't create the config file
+    state.run_command(Command::Filter(Some(String::from("("))));
+    state.run_command(Command::Unfilter(String::from("missing")));
+    state.run_command(Command::Unignore(String::from("bob")));
+    assert!(!path.exists());
+
+    state.run_command(Command::Ignore(Some(String::from("bob"))));
+    let file = FileConfig::read(&path).unwrap();
+    assert!(file.filters.ignore.contains("bob"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn check_send_while_disconnected() {
+    let server = MockServer::start().await.unwrap();
+    let (mut state, events, connection) = start_app(&server).await;
+
+    connection.disconnect();
+    handle_until(&mut state, &events, |event| {
+        matches!(event, AppEvent::Disconnected(_))
+    });
+    // Kept until there's a connection again instead of being written to the closed one
+    state.send_message(String::from("kept"));
+
+    state.reconnect();
+    let mut connection = server.accept().await.unwrap();
+    connection.expect(join("alice")).await.unwrap();
+    handle_until(&mut state, &events, |event| {
+        matches!(event, AppEvent::Reconnected(_))
+    });
+    connection.expect(message("alice", "kept")).await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn check_challenge_status() {
+    let server = MockServer::start().await.unwrap();
+    let (mut state, events, mut connection) = start_app(&server).await;
+    let received = |event: &AppEvent| matches!(event, AppEvent::PayloadReceived(_));
+
+    connection.send_challenge(4, "abc123").await.unwrap();
+    handle_until(&mut state, &events, received);
+    assert!(state.status.challenge_pending);
+    assert!(last_line(&state).contains("Your messages are dropped until the server rescinds it"));
+
+    connection.send_rescinded().await.unwrap();
+    handle_until(&mut state, &events, received);
+    assert!(!state.status.challenge_pending);
+
+    connection.send_error("Slow down").await.unwrap();
+    handle_until(&mut state, &events, received);
+    assert_eq!(state.status.last_error.as_deref(), Some("Slow down"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn check_record_and_replay() {
+    let capture = tempfile::NamedTempFile::new().unwrap();
+    let server = MockServer::start().await.unwrap();
+    let mut config = server.app_config("alice").unwrap();
+    config.client.recorder = Some(Recorder::create(capture.path()).unwrap());
+    let mut client = DspClient::start(&config.client).await.unwrap();
+    let mut connection = server.accept().await.unwrap();
+    connection.expect(join("alice")).await.unwrap();
+    connection.send(message("bob", "recorded")).await.unwrap();
+    client.reader.read().await.unwrap();
+
+    let records = read_capture(capture.path()).unwrap();
+    let frames: Vec<_> = records
+        .iter()
+        .map(|record| (record.direction, record.frame.as_str()))
+        .collect();
+    assert_eq!(
+        frames,
+        [
+            (Direction::ToServer, "alice JOIN"),
+            (Direction::ToClient, "bob MESSAGE recorded"),
+        ]
+    );
+
+    let replay = Replay::read(capture.path(), 100.0).unwrap();
+    let config = server.app_config("alice").unwrap();
+    let mut state = AppState::replay(replay, config.client, config.ui);
+    let events = state.start_headless().unwrap();
+    handle_until(&mut state, &events, |event| {
+        matches!(event, AppEvent::ReplayFinished())
+    });
+    let lines: Vec<_> = state.chat.lines().map(|line| line.to_string()).collect();
+    assert!(lines.contains(&String::from("[bob] recorded")));
+
+    state.send_message(String::from("not sent"));
+    assert!(last_line(&state).contains("Nothing is sent while replaying"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn check_history_across_sessions() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::start().await.unwrap();
+    let history_config = |server: &MockServer| {
+        let mut config = server.app_config("alice").unwrap();
+        config.client.history = Some(History::open(dir.path(), &HistoryConfig::default()).unwrap());
+        config
+    };
+    let received = |event: &AppEvent| matches!(event, AppEvent::PayloadReceived(_));
+
+    let (mut state, events, mut connection) =
+        start_app_with(&server, history_config(&server)).await;
+    for text in ["hi alice", "before restart"] {
+        connection.send(message("bob", text)).await.unwrap();
+        handle_until(&mut state, &events, received);
+    }
+    drop(state);
+
+    // Segments are capture files, so they can be replayed too
+    let records = read_capture(&dir.path().join("00000001.jsonl")).unwrap();
+    let frames: Vec<_> = records
+        .iter()
+        .map(|record| (record.direction, record.frame.as_str()))
+        .collect();
+    assert_eq!(
+        frames,
+        [
+            (Direction::ToServer, "alice JOIN"),
+            (Direction::ToClient, "bob MESSAGE hi alice"),
+            (Direction::ToClient, "bob MESSAGE before restart"),
+        ]
+    );
+
+    let (mut state, events, mut connection) =
+        start_app_with(&server, history_config(&server)).await;
+    let lines: Vec<_> = state.chat.lines().map(|line| line.to_string()).collect();
+    assert!(lines[0].contains("Messages from earlier sessions: 2"));
+    assert_eq!(lines[1..], ["[bob] hi alice", "[bob] before restart"]);
+
+    connection
+        .send(message("bob", "after restart"))
+        .await
+        .unwrap();
+    handle_until(&mut state, &events, received);
+    assert_eq!(last_line(&state), "[bob] after restart");
+    assert_eq!(state.status.received, 1);
+}
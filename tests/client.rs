@@ -0,0 +1,89 @@
+use uiop_dsp::{
+    client::DspClient,
+    mock::{MockServer, join, message, quit},
+    protocol::*,
+};
+
+#[tokio::test]
+async fn check_join_and_exchange() {
+    let server = MockServer::start().await.unwrap();
+    let mut client = DspClient::start(&server.client_config("alice"))
+        .await
+        .unwrap();
+    let mut connection = server.accept().await.unwrap();
+    connection.expect(join("alice")).await.unwrap();
+
+    connection.send(message("bob", "hi alice")).await.unwrap();
+    assert_eq!(
+        client.reader.read().await.unwrap(),
+        message("bob", "hi alice")
+    );
+
+    client
+        .writer
+        .write(message("alice", "hi bob"))
+        .await
+        .unwrap();
+    client.writer.write(quit("alice")).await.unwrap();
+    connection.expect(message("alice", "hi bob")).await.unwrap();
+    connection.expect(quit("alice")).await.unwrap();
+    drop(client);
+    connection.expect_closed().await.unwrap();
+}
+
+#[tokio::test]
+async fn check_server_errors() {
+    let server = MockServer::start().await.unwrap();
+    let mut client = DspClient::start(&server.client_config("alice"))
+        .await
+        .unwrap();
+    let mut connection = server.accept().await.unwrap();
+    connection.expect(join("alice")).await.unwrap();
+
+    connection.send_error("Username is taken").await.unwrap();
+    let DspMessage::ErrorMessage(error) = client.reader.read().await.unwrap().message else {
+        panic!("Expected an error");
+    };
+    assert_eq!(error.text, "Username is taken");
+
+    // Frames which aren't UTF-8 are skipped, ones which don't parse fail the read
+    connection
+        .send_raw(b"\xff\xfe\0not a payload\0")
+        .await
+        .unwrap();
+    assert!(client.reader.read().await.is_err());
+
+    connection.disconnect();
+    assert!(client.reader.read().await.is_err());
+}
+
+#[tokio::test]
+async fn check_challenge_exchange() {
+    let server = MockServer::start().await.unwrap();
+    let mut client = DspClient::start(&server.client_config("alice"))
+        .await
+        .unwrap();
+    let mut connection = server.accept().await.unwrap();
+    connection.expect(join("alice")).await.unwrap();
+
+    connection.send_challenge(12, "abc123").await.unwrap();
+    let challenge = DspMessage::ChallengeMessage(ChallengeMessage {
+        n: 12,
+        phrase: String::from("abc123"),
+    });
+    assert_eq!(client.reader.read().await.unwrap().message, challenge);
+
+    let response = DspMessage::ResponseMessage(ResponseMessage {
+        phrase: String::from("abc123xyz"),
+    });
+    let response = DspPayload {
+        username: String::from("alice"),
+        message: response,
+    };
+    client.writer.write(response.clone()).await.unwrap();
+    connection.expect(response).await.unwrap();
+
+    connection.send_rescinded().await.unwrap();
+    let rescinded = DspMessage::RescindedMessage(RescindedMessage {});
+    assert_eq!(client.reader.read().await.unwrap().message, rescinded);
+}
@@ -0,0 +1,165 @@
+use insta::{assert_snapshot, with_settings};
+use ratatui::{Terminal, backend::TestBackend};
+use uiop_dsp::{
+    app::{
+        crossterm_backend::{
+            Event, Key, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+        },
+        event::AppEvent,
+        main::App,
+    },
+    client::DspClient,
+    mock::{MockServer, join, message},
+};
+
+/// Drives an app with scripted events and renders it without a terminal
+struct Harness {
+    app: App,
+    terminal: Terminal<TestBackend>,
+    // Keeps the client's connection open
+    _server: MockServer,
+}
+
+impl Harness {
+    async fn start(width: u16, height: u16) -> Harness {
+        let server = MockServer::start().await.unwrap();
+        let mut config = server.app_config("alice").unwrap();
+        // OSC 8 sequences would end up in the buffer's cells
+        config.ui.links.hyperlinks = false;
+        let client = DspClient::start(&config.client).await.unwrap();
+        let mut app = App::new(client.reader, client.writer, config.client, config.ui);
+        app.handle_app_event(AppEvent::UiEvent(Event::Resize(width, height)));
+        let terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        Harness {
+            app,
+            terminal,
+            _server: server,
+        }
+    }
+
+    fn receive(&mut self, payload: uiop_dsp::protocol::DspPayload) {
+        self.app
+            .handle_app_event(AppEvent::PayloadReceived(payload));
+    }
+
+    fn press(&mut self, key: Key) {
+        let event = Event::Key(KeyEvent::new(key, KeyModifiers::NONE));
+        self.app.handle_app_event(AppEvent::UiEvent(event));
+    }
+
+    fn click(&mut self, column: u16, row: u16) {
+        let event = Event::Mouse(MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        });
+        self.app.handle_app_event(AppEvent::UiEvent(event));
+    }
+
+    fn type_text(&mut self, text: &str) {
+        text.chars().for_each(|c| self.press(Key::Char(c)));
+    }
+
+    /// Renders the app as text
+    fn render(&mut self) -> String {
+        self.app.draw(&mut self.terminal).unwrap();
+        self.terminal.backend().to_string()
+    }
+}
+
+/// Timestamps and the mock server's port, with the border it pads, change every run
+fn check_snapshot(name: &str, screen: String) {
+    let filters = vec![
+        (r"\d{2}:\d{2}:\d{2}", "hh:mm:ss"),
+        (r"127\.0\.0\.1:\d+─*", "[mock server]"),
+    ];
+    with_settings!({filters => filters}, {
+        assert_snapshot!(name, screen);
+    });
+}
+
+#[tokio::test]
+async fn render_chat() {
+    let mut harness = Harness::start(80, 24).await;
+    harness.receive(join("alice"));
+    harness.receive(join("bob"));
+    harness.receive(message("bob", "hi alice, how are you?"));
+    harness.receive(message("alice", "fine, thanks"));
+    harness.type_text("see you");
+    check_snapshot("chat", harness.render());
+}
+
+#[tokio::test]
+async fn render_long_messages() {
+    let mut harness = Harness::start(80, 24).await;
+    let long = "lorem ipsum dolor sit amet ".repeat(8);
+    harness.receive(message("bob", long.trim_end()));
+    harness.type_text(&"typing a very long prompt ".repeat(4));
+    check_snapshot("long_messages", harness.render());
+}
+
+#[tokio::test]
+async fn render_narrow_terminal() {
+    let mut harness = Harness::start(40, 12).await;
+    harness.receive(join("bob"));
+    harness.receive(message("bob", "a message which wraps in a narrow terminal"));
+    harness
+        .app
+        .handle_app_event(AppEvent::FatalError(String::from("Something broke")));
+    check_snapshot("narrow_terminal", harness.render());
+}
+
+#[tokio::test]
+async fn render_tab_switching() {
+    let mut harness = Harness::start(80, 24).await;
+    harness.receive(message("bob", "bye"));
+    harness.type_text("draft");
+    harness.render();
+    // Only the style tells which tab is selected, the text stays the same
+    let tab_styles = |harness: &Harness| {
+        let buffer = harness.terminal.backend().buffer();
+        (buffer[(2, 1)].style(), buffer[(12, 1)].style())
+    };
+    let (message_tab, quit_tab) = tab_styles(&harness);
+    assert_ne!(message_tab, quit_tab);
+
+    harness.press(Key::BackTab);
+    check_snapshot("quit_tab", harness.render());
+    assert_eq!(tab_styles(&harness), (quit_tab, message_tab));
+
+    harness.press(Key::BackTab);
+    harness.render();
+    assert_eq!(tab_styles(&harness), (message_tab, quit_tab));
+}
+
+#[tokio::test]
+async fn click_sender_and_tabs() {
+    let mut harness = Harness::start(80, 24).await;
+    harness.receive(message("bob", "hi alice"));
+    harness.render();
+    let prompt_row = |screen: &str| screen.lines().nth(19).unwrap().to_string();
+
+    // Clicking the username in "hh:mm:ss [bob] hi alice" addresses bob
+    harness.click(12, 4);
+    assert!(prompt_row(&harness.render()).starts_with("\"│bob: "));
+
+    // Typing is ignored on the Quit tab, until the Message tab is clicked again
+    harness.click(12, 1);
+    harness.type_text("x");
+    assert!(prompt_row(&harness.render()).starts_with("\"│bob:  "));
+    harness.click(3, 1);
+    harness.type_text("x");
+    assert!(prompt_row(&harness.render()).starts_with("\"│bob: x"));
+}
+
+#[tokio::test]
+async fn tabs_hidden_in_compact_layout() {
+    let mut harness = Harness::start(40, 12).await;
+    harness.render();
+    // Tab doesn't switch to the invisible Quit tab, so typing still reaches the prompt
+    harness.press(Key::BackTab);
+    harness.type_text("still here");
+    let screen = harness.render();
+    assert!(screen.lines().any(|row| row.contains("still here")));
+}